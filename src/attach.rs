@@ -0,0 +1,162 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::io::{self, ErrorKind, IsTerminal, Read};
+use std::path::Path;
+
+/// Rough characters per token, good enough for budgeting English text and logs
+const CHARS_PER_TOKEN: usize = 4;
+
+/// Data sent along with the request: its start and end, and how big it was
+pub struct Attachment {
+    /// Where it came from: "stdin" or the file path
+    pub label: String,
+    head: String,
+    tail: String,
+    /// Whether anything was dropped between `head` and `tail` while reading
+    gap: bool,
+    total_bytes: u64,
+}
+
+/// Decode bytes cut out of a larger text; invalid UTF-8 is replaced, and so are
+/// characters split at the cut, which are dropped
+fn decode(bytes: &[u8], cut_before: bool, cut_after: bool) -> String {
+    let text = String::from_utf8_lossy(bytes);
+    let mut text: &str = &text;
+    if cut_before {
+        text = text.trim_start_matches('\u{FFFD}');
+    }
+    if cut_after {
+        text = text.trim_end_matches('\u{FFFD}');
+    }
+    text.to_string()
+}
+
+/// Stream `reader`, keeping the first quarter of `max_bytes` and the last three quarters,
+/// since errors tend to be at the end of logs
+///
+/// Input containing NUL bytes is treated as binary and refused; other invalid UTF-8 is
+/// replaced with U+FFFD.
+fn read_bounded(label: &str, mut reader: impl Read, max_bytes: usize) -> Result<Attachment, String> {
+    let head_cap = max_bytes / 4;
+    let tail_cap = max_bytes - head_cap;
+    let mut head = Vec::new();
+    let mut tail = VecDeque::new();
+    let mut total_bytes = 0u64;
+    let mut buf = [0u8; 8192];
+    loop {
+        let n = match reader.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(format!("Couldn't read {label}: {e}")),
+        };
+        let chunk = &buf[..n];
+        if chunk.contains(&0) {
+            return Err(format!("{label} looks like binary data"));
+        }
+        total_bytes += n as u64;
+        let to_head = (head_cap - head.len()).min(n);
+        head.extend_from_slice(&chunk[..to_head]);
+        tail.extend(&chunk[to_head..]);
+        let excess = tail.len().saturating_sub(tail_cap);
+        tail.drain(..excess);
+    }
+    let tail: Vec<u8> = tail.into();
+    let gap = total_bytes > (head.len() + tail.len()) as u64;
+    if !gap {
+        let whole = [head, tail].concat();
+        return Ok(Attachment { label: label.to_string(), head: decode(&whole, false, false), tail: String::new(), gap, total_bytes });
+    }
+    Ok(Attachment { label: label.to_string(), head: decode(&head, false, true), tail: decode(&tail, true, false), gap, total_bytes })
+}
+
+/// Whether stdin is a pipe or a redirected file, as opposed to a terminal, socket or
+/// character device that may never reach end of file
+pub fn stdin_carries_data() -> bool {
+    let stdin = io::stdin();
+    if stdin.is_terminal() {
+        return false;
+    }
+    #[cfg(unix)]
+    {
+        use std::os::fd::AsFd;
+        use std::os::unix::fs::FileTypeExt;
+        let Ok(fd) = stdin.as_fd().try_clone_to_owned() else {
+            return false;
+        };
+        fs::File::from(fd).metadata().is_ok_and(|m| m.file_type().is_fifo() || m.is_file())
+    }
+    #[cfg(not(unix))]
+    true
+}
+
+/// Everything piped into aido, or None when stdin is a terminal or empty
+pub fn read_stdin(budget_tokens: usize) -> Result<Option<Attachment>, String> {
+    if !stdin_carries_data() {
+        return Ok(None);
+    }
+    let stdin = io::stdin();
+    let attachment = read_bounded("stdin", stdin.lock(), budget_tokens * CHARS_PER_TOKEN)?;
+    let empty = attachment.head.trim().is_empty() && attachment.tail.trim().is_empty();
+    Ok((!empty).then_some(attachment))
+}
+
+/// A file given with `--file`
+pub fn read_file(path: &Path, budget_tokens: usize) -> Result<Attachment, String> {
+    let label = path.display().to_string();
+    let file = fs::File::open(path).map_err(|e| format!("Couldn't read {label}: {e}"))?;
+    read_bounded(&label, file, budget_tokens * CHARS_PER_TOKEN)
+}
+
+/// At most `max_chars` of the attachment: all of it when it fits, otherwise its start,
+/// a note of how much was left out, and a longer stretch of its end
+fn excerpt(attachment: &Attachment, max_chars: usize) -> String {
+    let head_chars = max_chars / 4;
+    let tail_chars = max_chars - head_chars;
+    let (start, end): (String, String) = if attachment.gap {
+        let tail_len = attachment.tail.chars().count();
+        (attachment.head.chars().take(head_chars).collect(), attachment.tail.chars().skip(tail_len.saturating_sub(tail_chars)).collect())
+    } else {
+        let text = attachment.head.trim_end();
+        let total = text.chars().count();
+        if total <= max_chars {
+            return text.to_string();
+        }
+        (text.chars().take(head_chars).collect(), text.chars().skip(total - tail_chars).collect())
+    };
+    let omitted = attachment.total_bytes.saturating_sub((start.len() + end.len()) as u64);
+    format!("{start}\n[... {omitted} bytes omitted ...]\n{}", end.trim_end())
+}
+
+/// The request followed by each attachment, sharing `budget_tokens` between them
+pub fn with_attachments(request: &str, attachments: &[Attachment], budget_tokens: usize) -> String {
+    if attachments.is_empty() {
+        return request.to_string();
+    }
+    let per_attachment = budget_tokens * CHARS_PER_TOKEN / attachments.len();
+    let mut message = request.to_string();
+    for attachment in attachments {
+        let content = excerpt(attachment, per_attachment);
+        message.push_str(&format!("\n\nContents of {}:\n```\n{content}\n```", attachment.label));
+    }
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_the_real_end_of_large_input() {
+        let text = "a".repeat(100) + &"m".repeat(10_000) + &"z".repeat(100);
+        let attachment = read_bounded("log", text.as_bytes(), 200).unwrap();
+        let cut = excerpt(&attachment, 40);
+        assert!(cut.starts_with(&"a".repeat(10)));
+        assert!(cut.ends_with(&"z".repeat(30)));
+        assert!(cut.contains("[... 10160 bytes omitted ...]"), "{cut}");
+
+        let short = read_bounded("log", "short\n".as_bytes(), 200).unwrap();
+        assert_eq!(excerpt(&short, 40), "short");
+        assert!(read_bounded("bin", &b"ELF\0\x01"[..], 200).is_err());
+    }
+}
@@ -0,0 +1,88 @@
+use crate::models::ModelTarget;
+use genai::chat::ChatMessage;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// A cached answer on disk
+#[derive(Serialize, Deserialize)]
+struct Cached {
+    /// Seconds since the Unix epoch
+    created: u64,
+    answer: String,
+}
+
+/// On-disk answers keyed by everything that went into the request
+pub struct Cache {
+    dir: PathBuf,
+    ttl: Duration,
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
+
+impl Cache {
+    /// None when caching is turned off (`cache_ttl_secs: 0`) or there's no cache directory
+    pub fn new(ttl_secs: u64) -> Option<Self> {
+        if ttl_secs == 0 {
+            return None;
+        }
+        let dir = dirs::cache_dir()?.join("aido").join("responses");
+        fs::create_dir_all(&dir).ok()?;
+        Some(Cache { dir, ttl: Duration::from_secs(ttl_secs) })
+    }
+
+    /// Hash of the model with its provider and endpoint, shell, OS and the full message
+    /// history (system prompt included)
+    pub fn key(target: &ModelTarget, shell: &str, messages: &[ChatMessage]) -> String {
+        let mut hasher = Sha256::new();
+        let provider = target.kind().map_or_else(|_| String::new(), |kind| kind.to_string());
+        let endpoint = target.endpoint.as_deref().unwrap_or_default();
+        for part in [&target.model, &provider, endpoint, shell, std::env::consts::OS] {
+            hasher.update(part.as_bytes());
+            hasher.update([0]);
+        }
+        for msg in messages {
+            hasher.update(format!("{:?}", msg.role).as_bytes());
+            hasher.update([0]);
+            hasher.update(msg.content.text_as_str().unwrap_or_default().as_bytes());
+            hasher.update([0]);
+        }
+        hasher.finalize().iter().map(|b| format!("{b:02x}")).collect()
+    }
+
+    /// A fresh answer for `key`, with its age; stale or unreadable entries are deleted
+    pub fn get(&self, key: &str) -> Option<(String, Duration)> {
+        let path = self.dir.join(format!("{key}.json"));
+        let data = fs::read_to_string(&path).ok()?;
+        let fresh = serde_json::from_str::<Cached>(&data)
+            .ok()
+            .map(|cached| (cached.answer, Duration::from_secs(now_secs().saturating_sub(cached.created))))
+            .filter(|(_, age)| *age <= self.ttl);
+        if fresh.is_none() {
+            fs::remove_file(&path).ok();
+        }
+        fresh
+    }
+
+    /// Store an answer; failures are ignored since the cache is only an optimisation
+    pub fn put(&self, key: &str, answer: &str) {
+        let cached = Cached { created: now_secs(), answer: answer.to_string() };
+        if let Ok(data) = serde_json::to_string(&cached) {
+            fs::write(self.dir.join(format!("{key}.json")), data).ok();
+        }
+    }
+}
+
+/// Rough age for display, e.g. "3h"
+pub fn format_age(age: Duration) -> String {
+    match age.as_secs() {
+        s if s < 60 => format!("{s}s"),
+        s if s < 3600 => format!("{}m", s / 60),
+        s if s < 86400 => format!("{}h", s / 3600),
+        s => format!("{}d", s / 86400),
+    }
+}
@@ -0,0 +1,94 @@
+use crate::clean_answer;
+use crate::input::Input;
+use genai::chat::{ChatMessage, ChatRequest};
+use genai::Client;
+
+/// What the user wants after seeing the candidates
+#[derive(PartialEq, Debug)]
+pub enum Choice {
+    /// Go on with this candidate
+    Pick(usize),
+    /// Refine one candidate and continue with it alone
+    Refine(usize, String),
+    /// Refine the request and ask for new candidates
+    RefineAll(String),
+    /// Same request, fresh candidates
+    Regenerate,
+}
+
+/// Ask for `n` distinct commands in one structured response
+pub async fn generate(client: &Client, model: &str, messages: &[ChatMessage], n: usize) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let mut messages = messages.to_vec();
+    messages.push(ChatMessage::user(format!(
+        "Give {n} distinct alternative commands for this, each taking a different approach. \
+         Reply with only a JSON array of {n} strings, one complete command per string."
+    )));
+    let chat_res = client.exec_chat(model, ChatRequest::new(messages), None).await?;
+    let raw = chat_res.content_text_as_str().unwrap_or_default();
+    let parsed = match (raw.find('['), raw.rfind(']')) {
+        (Some(start), Some(end)) if start < end => serde_json::from_str::<Vec<String>>(&raw[start..=end]).ok(),
+        _ => None,
+    };
+    // fall back to one command per line if the model ignored the format
+    let mut candidates: Vec<String> = match parsed {
+        Some(list) => list.iter().map(|c| clean_answer(c)).collect(),
+        None => clean_answer(raw).lines().map(|l| l.trim().to_string()).collect(),
+    };
+    candidates.retain(|c| !c.is_empty());
+    let mut seen = Vec::new();
+    candidates.retain(|c| {
+        let fresh = !seen.contains(c);
+        seen.push(c.clone());
+        fresh
+    });
+    candidates.truncate(n);
+    if candidates.is_empty() {
+        candidates.push("NO ANSWER".to_string());
+    }
+    Ok(candidates)
+}
+
+/// Read the user's choice among `n` candidates; None when they cancel
+pub fn read_choice(input: &mut Input, n: usize) -> Option<Choice> {
+    println!("Pick 1-{n} (Enter for 1), '<number>: <text>' to refine one, text to refine all, r to regenerate, Ctrl+C to bail");
+    let line = input.read_line("> ")?;
+    Some(parse_choice(&line, n))
+}
+
+/// A bare number picks a candidate and `<number>: <text>` refines it; anything else
+/// refines the request, so "3 largest files" isn't mistaken for candidate 3
+fn parse_choice(line: &str, n: usize) -> Choice {
+    let input = line.trim();
+    if input.is_empty() {
+        return Choice::Pick(0);
+    }
+    if input == "r" {
+        return Choice::Regenerate;
+    }
+    let candidate = |number: &str| number.trim().parse::<usize>().ok().filter(|k| (1..=n).contains(k)).map(|k| k - 1);
+    if let Some(k) = candidate(input.trim_end_matches(['.', ')', ':'])) {
+        return Choice::Pick(k);
+    }
+    match input.split_once(':').and_then(|(number, rest)| Some((candidate(number)?, rest.trim()))) {
+        Some((k, rest)) if !rest.is_empty() => Choice::Refine(k, rest.to_string()),
+        _ => Choice::RefineAll(input.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_choices() {
+        assert_eq!(parse_choice("", 3), Choice::Pick(0));
+        assert_eq!(parse_choice(" 2 ", 3), Choice::Pick(1));
+        assert_eq!(parse_choice("3.", 3), Choice::Pick(2));
+        assert_eq!(parse_choice("r", 3), Choice::Regenerate);
+        assert_eq!(parse_choice("2: only .rs files", 3), Choice::Refine(1, "only .rs files".to_string()));
+        assert_eq!(parse_choice("3 largest files", 3), Choice::RefineAll("3 largest files".to_string()));
+        assert_eq!(parse_choice("4: too far", 3), Choice::RefineAll("4: too far".to_string()));
+        assert_eq!(parse_choice("5", 3), Choice::RefineAll("5".to_string()));
+        assert_eq!(parse_choice("note: keep it short", 3), Choice::RefineAll("note: keep it short".to_string()));
+    }
+}
@@ -0,0 +1,76 @@
+use base64::Engine;
+use std::env;
+use std::fs::OpenOptions;
+use std::io::{self, IsTerminal, Write};
+use std::process::{Command, Stdio};
+
+use crate::shell::find_executable;
+
+/// Local clipboard programs, tried in order; each reads the text on stdin
+const TOOLS: &[(&str, &[&str])] = &[
+    ("wl-copy", &[]),
+    ("xclip", &["-selection", "clipboard"]),
+    ("xsel", &["--clipboard", "--input"]),
+    ("pbcopy", &[]),
+    ("clip", &[]),
+];
+
+/// Put text on the clipboard with an OSC 52 escape sequence, which the terminal
+/// handles itself and so also works over SSH
+fn osc52(text: &str) -> io::Result<()> {
+    let encoded = base64::engine::general_purpose::STANDARD.encode(text);
+    let sequence = if env::var_os("TMUX").is_some() {
+        // tmux only forwards escape sequences wrapped in its passthrough
+        format!("\x1bPtmux;\x1b\x1b]52;c;{encoded}\x07\x1b\\")
+    } else {
+        format!("\x1b]52;c;{encoded}\x07")
+    };
+    // go straight to the terminal so `--print --copy | ...` doesn't put the sequence in the pipe
+    if let Ok(mut tty) = OpenOptions::new().write(true).open(if cfg!(windows) { "CONOUT$" } else { "/dev/tty" }) {
+        tty.write_all(sequence.as_bytes())?;
+        return tty.flush();
+    }
+    let mut out = io::stdout().lock();
+    if !out.is_terminal() {
+        return Err(io::Error::other("no terminal to send OSC 52 to"));
+    }
+    out.write_all(sequence.as_bytes())?;
+    out.flush()
+}
+
+/// Whether a local clipboard tool can reach a clipboard in this session
+fn has_local_clipboard() -> bool {
+    cfg!(any(windows, target_os = "macos")) || env::var_os("WAYLAND_DISPLAY").is_some() || env::var_os("DISPLAY").is_some()
+}
+
+/// Pipe text into the first local clipboard tool on PATH
+fn local_tool(text: &str) -> Option<&'static str> {
+    if !has_local_clipboard() {
+        return None;
+    }
+    TOOLS.iter().find_map(|(name, args)| {
+        let path = find_executable(name)?;
+        let mut child = Command::new(path).args(*args).stdin(Stdio::piped()).stdout(Stdio::null()).stderr(Stdio::null()).spawn().ok()?;
+        child.stdin.take()?.write_all(text.as_bytes()).ok()?;
+        child.wait().ok()?.success().then_some(*name)
+    })
+}
+
+/// Copy text to the clipboard, returning how it was done
+///
+/// OSC 52 goes to the terminal, which may be on the other end of an SSH session; a local tool
+/// (`wl-copy`, `xclip`, `pbcopy`, ...) is used as well when there's a clipboard here, since not
+/// every terminal honours OSC 52.
+pub fn copy(text: &str) -> Result<String, String> {
+    let mut used = Vec::new();
+    if osc52(text).is_ok() {
+        used.push("OSC 52");
+    }
+    if let Some(tool) = local_tool(text) {
+        used.push(tool);
+    }
+    if used.is_empty() {
+        return Err("no terminal for OSC 52 and no clipboard tool (wl-copy, xclip, xsel, pbcopy) found".to_string());
+    }
+    Ok(used.join(" + "))
+}
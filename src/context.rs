@@ -0,0 +1,234 @@
+use clap::ValueEnum;
+use serde::Deserialize;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Entries listed from the current directory
+const MAX_ENTRIES: usize = 40;
+/// Changed files listed from `git status`
+const MAX_GIT_CHANGES: usize = 15;
+/// Scripts or targets listed per tool
+const MAX_TASKS: usize = 20;
+/// Upper bound on the whole context section
+const MAX_CONTEXT_CHARS: usize = 4000;
+
+/// Something aido can tell the model about where the command will run
+#[derive(Copy, Clone, PartialEq, Eq, Debug, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Collector {
+    /// The current directory
+    Cwd,
+    /// A bounded listing of the current directory
+    Files,
+    /// Git branch and a summary of uncommitted changes
+    Git,
+    /// Build tools and task runners found in the project
+    Tools,
+    /// All of the above
+    All,
+}
+
+impl Collector {
+    fn expand(collectors: &[Collector]) -> Vec<Collector> {
+        if collectors.contains(&Collector::All) {
+            return vec![Collector::Cwd, Collector::Files, Collector::Git, Collector::Tools];
+        }
+        let mut expanded = Vec::new();
+        for &c in collectors {
+            if !expanded.contains(&c) {
+                expanded.push(c);
+            }
+        }
+        expanded
+    }
+}
+
+fn cwd() -> Option<String> {
+    Some(format!("Current directory: {}", env::current_dir().ok()?.display()))
+}
+
+fn files(dir: &Path) -> Option<String> {
+    let mut names: Vec<String> = fs::read_dir(dir)
+        .ok()?
+        .filter_map(|e| e.ok())
+        .map(|e| {
+            let name = e.file_name().to_string_lossy().into_owned();
+            if e.file_type().is_ok_and(|t| t.is_dir()) { format!("{name}/") } else { name }
+        })
+        .collect();
+    if names.is_empty() {
+        return Some("Directory is empty".to_string());
+    }
+    names.sort();
+    let total = names.len();
+    names.truncate(MAX_ENTRIES);
+    let mut listing = format!("Files here: {}", names.join(", "));
+    if total > MAX_ENTRIES {
+        listing.push_str(&format!(" (and {} more)", total - MAX_ENTRIES));
+    }
+    Some(listing)
+}
+
+/// `git status --branch --porcelain` condensed to the branch and a few changed paths
+fn git() -> Option<String> {
+    let output = Command::new("git").args(["status", "--branch", "--porcelain"]).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let text = String::from_utf8_lossy(&output.stdout);
+    let mut lines = text.lines();
+    let branch = lines.next()?.trim_start_matches("## ").to_string();
+    let changes: Vec<&str> = lines.collect();
+    let mut summary = format!("Git branch: {branch}");
+    if changes.is_empty() {
+        summary.push_str("\nWorking tree clean");
+    } else {
+        summary.push_str(&format!("\nUncommitted changes ({}):", changes.len()));
+        for change in changes.iter().take(MAX_GIT_CHANGES) {
+            summary.push_str(&format!("\n  {change}"));
+        }
+        if changes.len() > MAX_GIT_CHANGES {
+            summary.push_str("\n  ...");
+        }
+    }
+    Some(summary)
+}
+
+/// Target names from a Makefile, skipping special and pattern targets
+fn make_targets(makefile: &str) -> Vec<String> {
+    makefile
+        .lines()
+        .filter(|l| !l.starts_with(['\t', ' ', '#', '.']))
+        .filter_map(|l| l.split_once(':'))
+        .filter(|(name, rest)| !rest.starts_with('=') && !name.contains(['%', '$', '=']))
+        .flat_map(|(names, _)| names.split_whitespace().map(String::from).collect::<Vec<_>>())
+        .take(MAX_TASKS)
+        .collect()
+}
+
+/// Script names from package.json
+fn npm_scripts(package_json: &str) -> Vec<String> {
+    let Ok(value) = serde_json::from_str::<serde_json::Value>(package_json) else {
+        return Vec::new();
+    };
+    value["scripts"].as_object().map(|s| s.keys().take(MAX_TASKS).cloned().collect()).unwrap_or_default()
+}
+
+/// Which node package manager the lockfile points to
+fn node_package_manager(dir: &Path) -> &'static str {
+    [("pnpm-lock.yaml", "pnpm"), ("yarn.lock", "yarn"), ("bun.lockb", "bun"), ("bun.lock", "bun")]
+        .into_iter()
+        .find(|(lock, _)| dir.join(lock).is_file())
+        .map_or("npm", |(_, pm)| pm)
+}
+
+/// Project markers from the current directory up to the repository root
+fn tools(start: &Path) -> Option<String> {
+    let mut found = Vec::new();
+    let mut dir: Option<PathBuf> = Some(start.to_path_buf());
+    while let Some(d) = dir {
+        let has = |name: &str| d.join(name).is_file();
+        let rel = if d == start { String::new() } else { format!(" (in {})", d.display()) };
+        if has("Cargo.toml") {
+            found.push(format!("Rust project, use cargo{rel}"));
+        }
+        if let Ok(package) = fs::read_to_string(d.join("package.json")) {
+            let scripts = npm_scripts(&package);
+            let pm = node_package_manager(&d);
+            if scripts.is_empty() {
+                found.push(format!("Node project, use {pm}{rel}"));
+            } else {
+                found.push(format!("Node project, use {pm}{rel}; scripts: {}", scripts.join(", ")));
+            }
+        }
+        for makefile in ["Makefile", "makefile", "GNUmakefile"] {
+            if let Ok(text) = fs::read_to_string(d.join(makefile)) {
+                found.push(format!("Makefile{rel}; targets: {}", make_targets(&text).join(", ")));
+                break;
+            }
+        }
+        if let Some(compose) = ["docker-compose.yml", "docker-compose.yaml", "compose.yml", "compose.yaml"].into_iter().find(|f| has(f)) {
+            found.push(format!("Docker Compose ({compose}){rel}, use `docker compose`"));
+        }
+        if has("Dockerfile") {
+            found.push(format!("Dockerfile{rel}"));
+        }
+        if has("pyproject.toml") {
+            let runner = if has("uv.lock") { "uv" } else if has("poetry.lock") { "poetry" } else { "python/pip" };
+            found.push(format!("Python project, use {runner}{rel}"));
+        } else if has("requirements.txt") {
+            found.push(format!("Python project with requirements.txt{rel}"));
+        }
+        if has("go.mod") {
+            found.push(format!("Go module, use go{rel}"));
+        }
+        if has("justfile") || has("Justfile") {
+            found.push(format!("justfile{rel}, use just"));
+        }
+        if has("CMakeLists.txt") {
+            found.push(format!("CMake project{rel}"));
+        }
+        // stop at the repository root, or once something turned up
+        if !found.is_empty() || d.join(".git").exists() {
+            break;
+        }
+        dir = d.parent().map(Path::to_path_buf);
+    }
+    (!found.is_empty()).then(|| format!("Project tooling:\n{}", found.iter().map(|f| format!("  {f}")).collect::<Vec<_>>().join("\n")))
+}
+
+/// Run the chosen collectors and format a section for the system prompt; empty when none apply
+pub fn gather(collectors: &[Collector]) -> String {
+    let Ok(dir) = env::current_dir() else {
+        return String::new();
+    };
+    let parts: Vec<String> = Collector::expand(collectors)
+        .into_iter()
+        .filter_map(|c| match c {
+            Collector::Cwd => cwd(),
+            Collector::Files => files(&dir),
+            Collector::Git => git(),
+            Collector::Tools => tools(&dir),
+            Collector::All => None,
+        })
+        .collect();
+    if parts.is_empty() {
+        return String::new();
+    }
+    let mut section = format!("Context about where the command will run:\n{}", parts.join("\n"));
+    if section.len() > MAX_CONTEXT_CHARS {
+        let mut end = MAX_CONTEXT_CHARS;
+        while !section.is_char_boundary(end) {
+            end -= 1;
+        }
+        section.truncate(end);
+        section.push_str("\n[truncated]");
+    }
+    section
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_make_targets() {
+        let makefile = "CC := gcc\n.PHONY: test\nall: build\n\ttouch x\nbuild test: deps\n%.o: %.c\n# lint: nope\n";
+        assert_eq!(make_targets(makefile), ["all", "build", "test"]);
+    }
+
+    #[test]
+    fn reads_npm_scripts() {
+        assert_eq!(npm_scripts(r#"{"scripts": {"test": "jest", "build": "tsc"}}"#), ["build", "test"]);
+        assert!(npm_scripts("not json").is_empty());
+    }
+
+    #[test]
+    fn expands_collectors_once_each() {
+        use Collector::*;
+        assert_eq!(Collector::expand(&[Git, Cwd, Git, Files, Cwd]), [Git, Cwd, Files]);
+        assert_eq!(Collector::expand(&[Git, All]), [Cwd, Files, Git, Tools]);
+    }
+}
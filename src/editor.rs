@@ -0,0 +1,65 @@
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// `$VISUAL`, then `$EDITOR`, then the platform's usual fallback
+fn editor_command() -> String {
+    env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .ok()
+        .filter(|e| !e.trim().is_empty())
+        .unwrap_or_else(|| if cfg!(windows) { "notepad".to_string() } else { "vi".to_string() })
+}
+
+/// Create a fresh temp file only the user can read; `create_new` refuses to follow a
+/// symlink or reuse a file someone else planted in a shared temp dir
+fn create_temp_file(ext: &str) -> io::Result<(PathBuf, File)> {
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.subsec_nanos());
+    for attempt in 0..16u32 {
+        let path = env::temp_dir().join(format!("aido-{}-{nanos:x}{attempt}.{ext}", std::process::id()));
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        match options.open(&path) {
+            Ok(file) => return Ok((path, file)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Err(io::Error::new(io::ErrorKind::AlreadyExists, "couldn't create a temp file for the editor"))
+}
+
+/// Open `command` in the user's editor via a temp file and return the edited text
+pub fn edit(command: &str, ext: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let (path, mut file) = create_temp_file(ext)?;
+    let written = writeln!(file, "{command}").and_then(|_| file.flush());
+    drop(file);
+    if let Err(e) = written {
+        fs::remove_file(&path).ok();
+        return Err(e.into());
+    }
+
+    // allow editors with arguments, e.g. `code --wait`
+    let editor = editor_command();
+    let mut parts = editor.split_whitespace();
+    let program = parts.next().ok_or("No editor configured")?;
+    let status = {
+        let _guard = crate::run::ChildGuard::new();
+        Command::new(program).args(parts).arg(&path).status()
+    };
+
+    let edited = fs::read_to_string(&path);
+    fs::remove_file(&path).ok();
+    let status = status.map_err(|e| format!("Couldn't start editor '{editor}': {e}"))?;
+    if !status.success() {
+        return Err(format!("Editor '{editor}' exited with {status}").into());
+    }
+    Ok(edited?.trim_end().to_string())
+}
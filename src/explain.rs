@@ -0,0 +1,48 @@
+use crate::redact::Redactor;
+use crate::shell::Shell;
+use genai::chat::{ChatMessage, ChatRequest};
+use genai::Client;
+
+/// Separator the model is asked to put between a part and its explanation
+const SEPARATOR: &str = " :: ";
+
+/// Ask the model to break `command` down into its parts
+pub async fn explain(client: &Client, model: &str, shell: Shell, redactor: &Redactor, command: &str) -> Result<Vec<(String, String)>, Box<dyn std::error::Error>> {
+    let chat_req = ChatRequest::new(redactor.messages(&[
+        ChatMessage::system(format!(
+            "You explain {} commands to someone about to run them. Break the command into its meaningful parts \
+             (program, each flag, arguments, pipes, redirections) in order. Output one line per part in the form \
+             `<part>{SEPARATOR}<short explanation>`, with no other text, no code blocks and no backticks. \
+             End with a line `summary{SEPARATOR}<what the whole command does, including side effects>`.",
+            shell.display_name()
+        )),
+        ChatMessage::user(command.to_string()),
+    ]));
+    let chat_res = client.exec_chat(model, chat_req, None).await?;
+    let raw = chat_res.content_text_as_str().unwrap_or_default();
+    Ok(raw
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with("```"))
+        .map(|l| match l.split_once(SEPARATOR) {
+            Some((part, text)) => (part.trim().trim_matches('`').to_string(), text.trim().to_string()),
+            None => (String::new(), l.to_string()),
+        })
+        .collect())
+}
+
+/// Print the explanation as an annotated list beneath the preview box
+pub fn print_explanation(parts: &[(String, String)]) {
+    let width = parts.iter().map(|(p, _)| p.chars().count()).filter(|&n| n <= 24).max().unwrap_or(0);
+    for (part, text) in parts {
+        let len = part.chars().count();
+        if part.is_empty() {
+            println!("  {text}");
+        } else if len > width {
+            println!("  \x1b[1;36m{part}\x1b[0m");
+            println!("  {}  {text}", " ".repeat(width));
+        } else {
+            println!("  \x1b[1;36m{part}\x1b[0m{}  {text}", " ".repeat(width - len));
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use crate::shell::Shell;
+use clap::Args;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+/// Arguments of `aido fix`
+#[derive(Args)]
+pub struct FixArgs {
+    /// The command that failed (read from the shell's history file when omitted)
+    #[arg(long)]
+    pub command: Option<String>,
+
+    /// Its error output
+    #[arg(long)]
+    pub error: Option<String>,
+
+    /// Its exit status
+    #[arg(long)]
+    pub status: Option<i32>,
+}
+
+/// `$XDG_DATA_HOME`, or `~/.local/share` like most shells use even on macOS
+fn xdg_data_dir() -> Option<PathBuf> {
+    env::var_os("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| dirs::home_dir().map(|h| h.join(".local").join("share")))
+}
+
+/// Where a shell keeps its history, if it keeps one aido knows how to read
+fn history_file(shell: Shell) -> Option<PathBuf> {
+    let home = dirs::home_dir()?;
+    let histfile = env::var_os("HISTFILE").map(PathBuf::from);
+    match shell {
+        Shell::Bash => histfile.or_else(|| Some(home.join(".bash_history"))),
+        Shell::Zsh => histfile.or_else(|| {
+            let dir = env::var_os("ZDOTDIR").map(PathBuf::from).unwrap_or(home);
+            Some(dir.join(".zsh_history"))
+        }),
+        Shell::Fish => Some(xdg_data_dir()?.join("fish").join("fish_history")),
+        Shell::Nushell => Some(dirs::config_dir()?.join("nushell").join("history.txt")),
+        Shell::PowerShell if cfg!(windows) => {
+            Some(dirs::data_dir()?.join(r"Microsoft\Windows\PowerShell\PSReadLine\ConsoleHost_history.txt"))
+        }
+        Shell::PowerShell => Some(xdg_data_dir()?.join("powershell").join("PSReadLine").join("ConsoleHost_history.txt")),
+        Shell::Sh | Shell::Cmd => None,
+    }
+}
+
+/// Undo fish_history's escaping of backslashes and newlines
+fn unescape_fish(command: &str) -> String {
+    let mut out = String::with_capacity(command.len());
+    let mut chars = command.chars();
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some('n')) => {
+                out.push('\n');
+                chars.next();
+            }
+            ('\\', Some('\\')) => {
+                out.push('\\');
+                chars.next();
+            }
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Pull the command out of one history line, in the shell's own format
+fn parse_history_line(shell: Shell, line: &str) -> Option<String> {
+    let command = match shell {
+        // extended history: `: <start>:<elapsed>;<command>`
+        Shell::Zsh if line.starts_with(": ") => line.split_once(';')?.1.to_string(),
+        // fish_history is YAML-ish: `- cmd: <command>` followed by `  when: <time>`
+        Shell::Fish => unescape_fish(line.strip_prefix("- cmd: ")?),
+        _ => line.to_string(),
+    };
+    let command = command.trim();
+    // skip the `aido fix` invocation itself and bash timestamp lines
+    if command.is_empty() || command.starts_with('#') || matches!(command.split_whitespace().next(), Some("aido" | "aido_fix")) {
+        return None;
+    }
+    Some(command.to_string())
+}
+
+/// Most recent command in the shell's history, other than aido itself
+pub fn last_history_command(shell: Shell) -> Result<String, String> {
+    let path = history_file(shell)
+        .ok_or_else(|| format!("aido can't read {} history; pass the command with --command", shell.display_name()))?;
+    let bytes = fs::read(&path).map_err(|e| format!("Couldn't read {}: {e}; pass the command with --command", path.display()))?;
+    String::from_utf8_lossy(&bytes)
+        .lines()
+        .rev()
+        .find_map(|line| parse_history_line(shell, line))
+        .ok_or_else(|| format!("No previous command in {}; pass it with --command", path.display()))
+}
+
+/// User message asking the model to repair the failed command
+pub fn fix_prompt(args: &FixArgs, shell: Shell) -> Result<String, String> {
+    let command = match &args.command {
+        Some(command) if !command.trim().is_empty() => command.clone(),
+        _ => {
+            // bash and zsh only write their history file on exit by default
+            let command = last_history_command(shell)?;
+            eprintln!(
+                "aido: using `{command}` from your {} history file, which may be from an older session; \
+                 `aido_fix` from `aido init` passes the command that just failed",
+                shell.display_name()
+            );
+            command
+        }
+    };
+    let command = command.trim();
+    let mut prompt = format!("This command failed:\n{command}\n");
+    if let Some(status) = args.status {
+        prompt.push_str(&format!("It exited with status {status}.\n"));
+    }
+    match args.error.as_deref().map(str::trim) {
+        Some(error) if !error.is_empty() => prompt.push_str(&format!("Its error output was:\n{error}\n")),
+        _ => prompt.push_str("Its error output wasn't captured; work out the likely mistake from the command itself.\n"),
+    }
+    prompt.push_str("Give a corrected command that does what was intended.");
+    Ok(prompt)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_history_lines() {
+        assert_eq!(parse_history_line(Shell::Bash, "git push origin main").as_deref(), Some("git push origin main"));
+        assert_eq!(parse_history_line(Shell::Bash, "#1700000000"), None);
+        assert_eq!(parse_history_line(Shell::Zsh, ": 1700000000:0;make test").as_deref(), Some("make test"));
+        assert_eq!(parse_history_line(Shell::Zsh, "ls -la").as_deref(), Some("ls -la"));
+        assert_eq!(parse_history_line(Shell::Fish, "- cmd: echo a\\nb\\\\c").as_deref(), Some("echo a\nb\\c"));
+        assert_eq!(parse_history_line(Shell::Fish, "  when: 1700000000"), None);
+        assert_eq!(parse_history_line(Shell::Zsh, ": 1700000000:0;aido fix"), None);
+        assert_eq!(parse_history_line(Shell::Bash, "aido_fix"), None);
+    }
+}
@@ -0,0 +1,195 @@
+use crate::get_config_path;
+use clap::{Args, Subcommand};
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+
+/// One generated command, as stored in history.jsonl
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Entry {
+    pub id: u64,
+    pub timestamp: String,
+    pub prompt: String,
+    #[serde(default)]
+    pub refinements: Vec<String>,
+    pub model: String,
+    pub shell: String,
+    pub command: String,
+    pub cwd: String,
+    /// None when the command was only printed or previewed
+    pub exit_status: Option<i32>,
+}
+
+/// Arguments of `aido history`
+#[derive(Args)]
+pub struct HistoryArgs {
+    #[command(subcommand)]
+    pub action: Option<HistoryAction>,
+
+    /// Only show entries whose prompt or command contains all of these words
+    #[arg(short, long = "search", value_name = "WORDS", num_args = 1..)]
+    pub query: Vec<String>,
+
+    /// How many of the most recent matches to show
+    #[arg(long, default_value_t = 20)]
+    pub limit: usize,
+
+    /// Only show commands that exited with an error
+    #[arg(long)]
+    pub failed: bool,
+}
+
+/// Subcommands of `aido history`
+#[derive(Subcommand)]
+pub enum HistoryAction {
+    /// Preview an entry's command again and run it, without calling the model
+    Run { id: u64 },
+}
+
+/// How much of the end of history.jsonl to read when looking for the newest id
+const TAIL_BYTES: u64 = 64 * 1024;
+
+/// history.jsonl, next to config.json
+fn history_path() -> PathBuf {
+    get_config_path().with_file_name("history.jsonl")
+}
+
+/// All entries, oldest first; unreadable lines are skipped
+pub fn load() -> io::Result<Vec<Entry>> {
+    let data = match fs::read_to_string(history_path()) {
+        Ok(data) => data,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    Ok(data.lines().filter_map(|line| serde_json::from_str(line).ok()).collect())
+}
+
+/// Look up an entry by id
+pub fn find(id: u64) -> Result<Entry, String> {
+    load()
+        .map_err(|e| format!("Couldn't read history: {e}"))?
+        .into_iter()
+        .find(|e| e.id == id)
+        .ok_or_else(|| format!("No history entry {id}"))
+}
+
+/// Id of the newest entry in `file`, reading only its end unless no whole entry fits there
+fn last_id(file: &mut File) -> io::Result<u64> {
+    let len = file.seek(SeekFrom::End(0))?;
+    for start in [len.saturating_sub(TAIL_BYTES), 0] {
+        file.seek(SeekFrom::Start(start))?;
+        let mut data = Vec::new();
+        file.read_to_end(&mut data)?;
+        let newest = String::from_utf8_lossy(&data).lines().rev().find_map(|line| serde_json::from_str::<Entry>(line).ok());
+        if let Some(entry) = newest {
+            return Ok(entry.id);
+        }
+        if start == 0 {
+            break;
+        }
+    }
+    Ok(0)
+}
+
+/// What the current run knows so far, recorded once a command is final
+pub struct Draft {
+    pub enabled: bool,
+    pub prompt: String,
+    pub refinements: Vec<String>,
+    pub model: String,
+    pub shell: String,
+}
+
+impl Draft {
+    /// Append the final command to history; failures only warn
+    pub fn record(&self, command: &str, exit_status: Option<i32>) {
+        if !self.enabled {
+            return;
+        }
+        if let Err(e) = self.append(command, exit_status) {
+            eprintln!("Warning: couldn't save history: {e}");
+        }
+    }
+
+    fn append(&self, command: &str, exit_status: Option<i32>) -> Result<(), Box<dyn std::error::Error>> {
+        // the lock keeps aido running in two terminals from handing out the same id
+        let mut file = OpenOptions::new().create(true).read(true).append(true).open(history_path())?;
+        file.lock()?;
+        let entry = Entry {
+            id: last_id(&mut file)? + 1,
+            timestamp: chrono::Local::now().to_rfc3339(),
+            prompt: self.prompt.clone(),
+            refinements: self.refinements.clone(),
+            model: self.model.clone(),
+            shell: self.shell.clone(),
+            command: command.to_string(),
+            cwd: env::current_dir().map(|p| p.display().to_string()).unwrap_or_default(),
+            exit_status,
+        };
+        writeln!(file, "{}", serde_json::to_string(&entry)?)?;
+        Ok(())
+    }
+}
+
+/// List matching entries, newest last (`aido history`)
+pub fn print_history(args: &HistoryArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let words: Vec<String> = args.query.iter().map(|w| w.to_lowercase()).collect();
+    let matches: Vec<Entry> = load()?
+        .into_iter()
+        .filter(|e| !args.failed || e.exit_status.is_some_and(|s| s != 0))
+        .filter(|e| {
+            let haystack = format!("{}\n{}\n{}", e.prompt, e.refinements.join("\n"), e.command).to_lowercase();
+            words.iter().all(|w| haystack.contains(w.as_str()))
+        })
+        .collect();
+    if matches.is_empty() {
+        println!("No matching history.");
+    }
+    for e in &matches[matches.len().saturating_sub(args.limit)..] {
+        let when = chrono::DateTime::parse_from_rfc3339(&e.timestamp)
+            .map(|t| t.format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or_else(|_| e.timestamp.clone());
+        let status = match e.exit_status {
+            Some(0) => "\x1b[32m  ok  \x1b[0m".to_string(),
+            Some(code) => format!("\x1b[31mexit {code:<2}\x1b[0m"),
+            None => "  --  ".to_string(),
+        };
+        println!("{:>4}  {when}  {status}  {}", e.id, e.command.replace('\n', " ⏎ "));
+        println!("      \x1b[2m{} · {} · {}\x1b[0m", e.prompt.lines().next().unwrap_or_default(), e.shell, e.cwd);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_the_newest_id_past_long_entries() {
+        let path = env::temp_dir().join(format!("aido-history-test-{}.jsonl", std::process::id()));
+        let entry = |id, command: &str| {
+            let e = Entry {
+                id,
+                timestamp: String::new(),
+                prompt: String::new(),
+                refinements: Vec::new(),
+                model: String::new(),
+                shell: String::new(),
+                command: command.to_string(),
+                cwd: String::new(),
+                exit_status: None,
+            };
+            serde_json::to_string(&e).unwrap() + "\n"
+        };
+        fs::write(&path, entry(6, "ls") + &entry(7, &"x".repeat(2 * TAIL_BYTES as usize))).unwrap();
+        let mut file = File::open(&path).unwrap();
+        assert_eq!(last_id(&mut file).unwrap(), 7);
+        fs::write(&path, entry(6, "ls") + &entry(7, "pwd") + "not json\n").unwrap();
+        assert_eq!(last_id(&mut File::open(&path).unwrap()).unwrap(), 7);
+        fs::write(&path, "").unwrap();
+        assert_eq!(last_id(&mut File::open(&path).unwrap()).unwrap(), 0);
+        fs::remove_file(&path).ok();
+    }
+}
@@ -0,0 +1,104 @@
+use crate::shell::Shell;
+
+/// bash: `eval "$(aido init bash)"` in ~/.bashrc
+const BASH: &str = r#"# aido shell integration: type a request, press Alt+A to turn it into a command
+_aido_widget() {
+    [[ -z "$READLINE_LINE" ]] && return
+    local cmd
+    cmd=$(aido --print --no-stdin --shell bash -- "$READLINE_LINE") || return
+    READLINE_LINE=$cmd
+    READLINE_POINT=${#READLINE_LINE}
+}
+bind -x '"\ea": _aido_widget'
+
+# remember the last command and its status at each prompt, for `aido_fix`
+_aido_remember() {
+    local exit_code=$? cmd
+    cmd=$(fc -ln -1)
+    cmd=${cmd#"${cmd%%[![:space:]]*}"}
+    [[ $cmd == aido_fix* ]] || { _aido_status=$exit_code; _aido_last=$cmd; }
+    return $exit_code
+}
+PROMPT_COMMAND="_aido_remember${PROMPT_COMMAND:+;$PROMPT_COMMAND}"
+aido_fix() { aido fix --status "${_aido_status:-1}" --command "$_aido_last" "$@"; }
+"#;
+
+/// zsh: `eval "$(aido init zsh)"` in ~/.zshrc
+const ZSH: &str = r#"# aido shell integration: type a request, press Alt+A to turn it into a command
+_aido_widget() {
+    [[ -z "$BUFFER" ]] && return
+    local cmd
+    if cmd=$(aido --print --no-stdin --shell zsh -- "$BUFFER"); then
+        BUFFER=$cmd
+        CURSOR=${#BUFFER}
+    fi
+    zle reset-prompt
+}
+zle -N _aido_widget
+bindkey '\ea' _aido_widget
+
+# remember the last command and its status, for `aido_fix`
+_aido_preexec() { [[ $1 == aido_fix* ]] || _aido_cmd=$1; }
+_aido_precmd() {
+    local exit_code=$?
+    [[ -n $_aido_cmd ]] && { _aido_status=$exit_code; _aido_last=$_aido_cmd; _aido_cmd=; }
+}
+preexec_functions+=(_aido_preexec)
+precmd_functions=(_aido_precmd $precmd_functions)
+aido_fix() { aido fix --status "${_aido_status:-1}" --command "$_aido_last" "$@"; }
+"#;
+
+/// fish: `aido init fish | source` in ~/.config/fish/config.fish
+const FISH: &str = r#"# aido shell integration: type a request, press Alt+A to turn it into a command
+function _aido_widget
+    set -l buf (commandline)
+    test -z "$buf"; and return
+    set -l cmd (aido --print --no-stdin --shell fish -- "$buf" | string collect)
+    and commandline -r -- $cmd
+    commandline -f repaint
+end
+bind \ea _aido_widget
+
+# remember the last command and its status, for `aido_fix`
+function _aido_remember --on-event fish_postexec
+    set -l last_status $status
+    string match -q 'aido_fix*' -- $argv[1]; and return
+    set -g _aido_status $last_status
+    set -g _aido_last $argv[1]
+end
+function aido_fix
+    set -q _aido_status; or set -g _aido_status 1
+    aido fix --status $_aido_status --command "$_aido_last" $argv
+end
+"#;
+
+/// PowerShell: `aido init pwsh | Out-String | Invoke-Expression` in $PROFILE
+const PWSH: &str = r#"# aido shell integration: type a request, press Alt+A to turn it into a command
+Set-PSReadLineKeyHandler -Chord 'Alt+a' -BriefDescription 'aido' -Description 'Replace the line with an aido command' -ScriptBlock {
+    $line = $null
+    $cursor = $null
+    [Microsoft.PowerShell.PSConsoleReadLine]::GetBufferState([ref]$line, [ref]$cursor)
+    if ([string]::IsNullOrWhiteSpace($line)) { return }
+    $cmd = (aido --print --no-stdin --shell powershell -- $line) -join "`n"
+    if ($LASTEXITCODE -eq 0 -and $cmd) {
+        [Microsoft.PowerShell.PSConsoleReadLine]::Replace(0, $line.Length, $cmd)
+    }
+}
+
+# fix the last command with its real exit status
+function aido_fix {
+    $status = if ($LASTEXITCODE) { $LASTEXITCODE } else { 1 }
+    aido fix --status $status --command (Get-History -Count 1).CommandLine @args
+}
+"#;
+
+/// Widget that puts aido's answer in the shell's line editor (`aido init <shell>`)
+pub fn script(shell: Shell) -> Result<&'static str, String> {
+    match shell {
+        Shell::Bash => Ok(BASH),
+        Shell::Zsh => Ok(ZSH),
+        Shell::Fish => Ok(FISH),
+        Shell::PowerShell => Ok(PWSH),
+        other => Err(format!("No shell integration for {} yet; bash, zsh, fish and pwsh are supported.", other.display_name())),
+    }
+}
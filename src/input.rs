@@ -0,0 +1,147 @@
+use rustyline::completion::{Completer, Pair};
+use rustyline::config::Behavior;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{CompletionType, Config, Context, EditMode, Editor, Helper};
+
+/// Slash commands available at the refine prompt, with their help text
+pub const SLASH_COMMANDS: &[(&str, &str)] = &[
+    ("/explain", "explain the command part by part"),
+    ("/edit", "open the command in $VISUAL/$EDITOR"),
+    ("/copy", "copy the command to the clipboard"),
+    ("/regen", "ask the model again for a fresh answer"),
+    ("/model", "switch model, e.g. /model gemini"),
+    ("/shell", "switch shell, e.g. /shell fish"),
+    ("/help", "list these commands"),
+    ("/quit", "exit without running anything"),
+];
+
+/// A parsed slash command
+#[derive(PartialEq, Debug)]
+pub enum Slash {
+    Explain,
+    Edit,
+    Copy,
+    Regen,
+    Model(String),
+    Shell(String),
+    Help,
+    Quit,
+}
+
+/// Parse a line starting with `/`; None for ordinary refinements
+pub fn parse_slash(line: &str) -> Option<Result<Slash, String>> {
+    let line = line.trim();
+    if !line.starts_with('/') {
+        return None;
+    }
+    let (name, arg) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    let arg = arg.trim().to_string();
+    let needs_arg = |what: &str| Err(format!("{name} needs a {what}"));
+    Some(match name {
+        "/explain" => Ok(Slash::Explain),
+        "/edit" => Ok(Slash::Edit),
+        "/copy" => Ok(Slash::Copy),
+        "/regen" => Ok(Slash::Regen),
+        "/model" if arg.is_empty() => needs_arg("model name or alias"),
+        "/model" => Ok(Slash::Model(arg)),
+        "/shell" if arg.is_empty() => needs_arg("shell name"),
+        "/shell" => Ok(Slash::Shell(arg)),
+        "/help" => Ok(Slash::Help),
+        "/quit" | "/exit" => Ok(Slash::Quit),
+        _ => Err(format!("Unknown command {name}; /help lists them")),
+    })
+}
+
+/// Tab completion for slash commands and their arguments
+struct SlashHelper {
+    models: Vec<String>,
+    shells: Vec<String>,
+}
+
+impl Completer for SlashHelper {
+    type Candidate = Pair;
+
+    fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let line = &line[..pos];
+        if !line.starts_with('/') {
+            return Ok((pos, Vec::new()));
+        }
+        let pair = |s: &str| Pair { display: s.to_string(), replacement: s.to_string() };
+        let (start, options): (usize, Vec<&str>) = match line.split_once(' ') {
+            None => (0, SLASH_COMMANDS.iter().map(|(name, _)| *name).collect()),
+            Some(("/model", _)) => (line.rfind(' ').map_or(pos, |i| i + 1), self.models.iter().map(String::as_str).collect()),
+            Some(("/shell", _)) => (line.rfind(' ').map_or(pos, |i| i + 1), self.shells.iter().map(String::as_str).collect()),
+            Some(_) => return Ok((pos, Vec::new())),
+        };
+        let word = &line[start..];
+        Ok((start, options.into_iter().filter(|o| o.starts_with(word)).map(pair).collect()))
+    }
+}
+
+impl Hinter for SlashHelper {
+    type Hint = String;
+}
+
+impl Highlighter for SlashHelper {}
+
+impl Validator for SlashHelper {}
+
+impl Helper for SlashHelper {}
+
+/// Line editor for everything aido asks the user, with in-session history
+pub struct Input {
+    editor: Editor<SlashHelper, DefaultHistory>,
+}
+
+impl Input {
+    /// `vi` selects vi keybindings, anything else emacs; `models` and `shells` feed tab completion
+    pub fn new(edit_mode: &str, models: Vec<String>, shells: Vec<String>) -> rustyline::Result<Self> {
+        let config = Config::builder()
+            .edit_mode(if edit_mode.eq_ignore_ascii_case("vi") { EditMode::Vi } else { EditMode::Emacs })
+            .completion_type(CompletionType::List)
+            .auto_add_history(false)
+            // stdin may be carrying piped data, so talk to the terminal directly
+            .behavior(Behavior::PreferTerm)
+            .build();
+        let mut editor = Editor::with_config(config)?;
+        editor.set_helper(Some(SlashHelper { models, shells }));
+        Ok(Input { editor })
+    }
+
+    /// Read one line; None when the user cancels with Ctrl+C or Ctrl+D
+    pub fn read_line(&mut self, prompt: &str) -> Option<String> {
+        match self.editor.readline(prompt) {
+            Ok(line) => {
+                if !line.trim().is_empty() {
+                    self.editor.add_history_entry(line.trim()).ok();
+                }
+                Some(line)
+            }
+            Err(ReadlineError::Interrupted | ReadlineError::Eof) => None,
+            Err(e) => {
+                eprintln!("Error: {e}");
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_slash_commands() {
+        assert_eq!(parse_slash("only .rs files"), None);
+        assert_eq!(parse_slash(" /regen "), Some(Ok(Slash::Regen)));
+        assert_eq!(parse_slash("/exit"), Some(Ok(Slash::Quit)));
+        assert_eq!(parse_slash("/model  gemini "), Some(Ok(Slash::Model("gemini".to_string()))));
+        assert_eq!(parse_slash("/shell fish"), Some(Ok(Slash::Shell("fish".to_string()))));
+        assert_eq!(parse_slash("/model"), Some(Err("/model needs a model name or alias".to_string())));
+        assert_eq!(parse_slash("/nope"), Some(Err("Unknown command /nope; /help lists them".to_string())));
+    }
+}
@@ -1,52 +1,239 @@
-use clap::{Parser, ValueEnum};
-use genai::chat::{ChatMessage, ChatRequest};
-use genai::Client;
+mod attach;
+mod cache;
+mod candidates;
+mod clipboard;
+mod context;
+mod editor;
+mod explain;
+mod fix;
+mod history;
+mod init;
+mod input;
+mod models;
+mod preview;
+mod redact;
+mod risk;
+mod run;
+mod shell;
+mod syntax;
+mod tools;
+
+use cache::{format_age, Cache};
+use clap::error::ErrorKind;
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+use context::Collector;
+use genai::adapter::AdapterKind;
+use futures::StreamExt;
+use genai::chat::{ChatMessage, ChatRequest, ChatStreamEvent, StreamChunk};
+use genai::resolver::{AuthData, AuthResolver, Endpoint, ModelMapper, ServiceTargetResolver};
+use genai::{Client, ModelIden, ServiceTarget};
+use input::{parse_slash, Input, Slash, SLASH_COMMANDS};
+use models::{resolve_model, ModelAlias, ModelTarget};
+use preview::{print_highlighted_code, LivePreview, Note, NoteStyle};
+use redact::{RedactConfig, Redactor};
+use risk::{RiskAnalyzer, RiskConfig, Severity};
+use shell::{detect_shell, shell_from_name, Shell, ShellEnv};
 use serde::Deserialize;
-use std::{env, fs, path::PathBuf, io::{self, Write}};
-use std::process::{Command, exit};
-use syntect::easy::HighlightLines;
-use syntect::highlighting::ThemeSet;
-use syntect::parsing::SyntaxSet;
-use syntect::util::as_24_bit_terminal_escaped;
-use terminal_size::terminal_size;
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::{env, fs, path::PathBuf, io::{self, IsTerminal}};
+use std::process::exit;
+use std::sync::{Arc, Mutex};
 use dirs::config_dir;
 
 /// CLI argument definitions
 #[derive(Parser)]
 #[command(name = "aido", author, version, about = "AI‑powered one‑liner for your shell")]
+#[command(subcommand_negates_reqs = true, disable_help_subcommand = true)]
 struct Cli {
+    #[command(subcommand)]
+    command: Option<Commands>,
+
     /// The question/prompt to send
     #[arg(required = true)]
     prompt: Vec<String>,
 
     /// Which model to call (overrides config)
-    #[arg(long)]
+    #[arg(long, global = true)]
     model: Option<String>,
 
-    /// Shell to generate commands for
-    #[arg(long, value_enum, default_value_t = Shell::PowerShell)]
-    shell: Shell,
+    /// Shell to generate commands for (detected when omitted)
+    #[arg(long, value_enum, global = true)]
+    shell: Option<Shell>,
 
-    /// Print the generated command without executing it
-    #[arg(long)]
+    /// Show the generated command without executing it
+    #[arg(long, global = true)]
     dry_run: bool,
+
+    /// Print only the command to stdout: no box, no prompt, nothing executed
+    #[arg(long, global = true, conflicts_with_all = ["dry_run", "yes"])]
+    print: bool,
+
+    /// Run the generated command without asking (high-risk commands still need confirmation)
+    #[arg(short, long, global = true, conflicts_with = "dry_run")]
+    yes: bool,
+
+    /// Also write the executed command's stdout and stderr to this file
+    #[arg(long, value_name = "FILE", global = true)]
+    capture: Option<PathBuf>,
+
+    /// When the command fails, send its error back to the model and try again
+    #[arg(long, global = true)]
+    fix: bool,
+
+    /// Offer this many alternative commands to pick from
+    #[arg(short = 'n', long, default_value_t = 1, value_parser = clap::value_parser!(u8).range(1..=9), global = true)]
+    candidates: u8,
+
+    /// Always ask the model, ignoring cached answers
+    #[arg(long, global = true)]
+    no_cache: bool,
+
+    /// Explain each generated command part by part before asking to run it
+    #[arg(long, global = true)]
+    explain: bool,
+
+    /// Attach a file's contents to the request (repeatable)
+    #[arg(short, long = "file", value_name = "PATH", global = true)]
+    files: Vec<PathBuf>,
+
+    /// Don't read piped stdin as an attachment (for editor integrations and `while read` loops)
+    #[arg(long, global = true)]
+    no_stdin: bool,
+
+    /// Tell the model about the project: cwd, files, git, tools or all (comma-separated)
+    #[arg(long, value_enum, value_delimiter = ',', value_name = "COLLECTORS", global = true)]
+    context: Vec<Collector>,
+
+    /// Report on stderr which secrets were masked before sending
+    #[arg(long, global = true)]
+    show_redactions: bool,
+
+    /// Copy the accepted command to the clipboard instead of running it
+    #[arg(long, global = true, conflicts_with = "yes")]
+    copy: bool,
+}
+
+/// Whether there's a terminal to ask the user on: stdin itself, or the controlling
+/// terminal when stdin carries piped data
+fn terminal_available() -> bool {
+    io::stdin().is_terminal() || (cfg!(unix) && fs::OpenOptions::new().read(true).write(true).open("/dev/tty").is_ok())
+}
+
+/// How much aido interacts with the terminal
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Mode {
+    /// Preview, refine, then run on Enter
+    Interactive,
+    /// Only the command on stdout, nothing executed
+    Print,
+    /// Preview only, nothing executed
+    DryRun,
+    /// Preview and run without asking
+    Yes,
 }
 
-/// Supported shells for execution
-#[derive(Copy, Clone, ValueEnum)]
-enum Shell {
-    Bash,
-    PowerShell,
+impl Mode {
+    /// Explicit flags win; otherwise drop to non-interactive modes when not attached to a terminal
+    fn from_cli(cli: &Cli) -> Mode {
+        if cli.print {
+            Mode::Print
+        } else if cli.dry_run {
+            Mode::DryRun
+        } else if cli.yes {
+            Mode::Yes
+        } else if !io::stdout().is_terminal() {
+            Mode::Print
+        } else if !terminal_available() {
+            Mode::DryRun
+        } else {
+            Mode::Interactive
+        }
+    }
 }
 
+/// Subcommands besides the default prompt mode
+#[derive(Subcommand)]
+enum Commands {
+    /// List configured model aliases and what they resolve to
+    Models,
+    /// Repair the last failed shell command
+    Fix(fix::FixArgs),
+    /// Search past commands, or re-run one with `history run <id>`
+    History(history::HistoryArgs),
+    /// Print a widget that puts aido's command in your prompt line (bash, zsh, fish, pwsh)
+    Init {
+        #[arg(value_enum, value_name = "SHELL")]
+        target: Shell,
+    },
+}
+
+/// Built-in system prompt template, used when the config doesn't set one
+const DEFAULT_SYSTEM_PROMPT: &str = "Give a {shell} one-liner to answer the question. The command will run on {os} {arch}. Do not use a code block or backticks. {shell_notes}";
+
+/// Placeholder prompt written by older versions of `ensure_config_exists`
+const LEGACY_SYSTEM_PROMPT: &str = "Answer in one sentence";
+
 /// Configuration loaded from file
 #[derive(Deserialize)]
 struct Config {
-    models: std::collections::HashMap<String, String>,
-    api_keys: std::collections::HashMap<String, String>,
+    /// Aliases usable with `--model`
+    models: HashMap<String, ModelAlias>,
+    api_keys: HashMap<String, String>,
     default_model: String,
     streaming: bool,
+    /// Shell to use when `--shell` isn't given, instead of detecting one
+    #[serde(default)]
+    default_shell: Option<String>,
+    /// Interpreter path per shell, e.g. `{ "powershell": "/opt/microsoft/powershell/7/pwsh" }`
+    #[serde(default)]
+    shell_paths: HashMap<String, String>,
+    /// Template with `{shell}`, `{shell_notes}`, `{os}`, `{arch}`, `{cwd}`, `{user}` and `{date}` placeholders
+    #[serde(default)]
     system_prompt: String,
+    /// Extra allow/deny regexes for the dangerous-command check
+    #[serde(default)]
+    risk: RiskConfig,
+    /// How many corrected commands `--fix` asks for before giving up
+    #[serde(default = "default_max_fix_attempts")]
+    max_fix_attempts: u32,
+    /// How long cached answers stay valid; 0 turns the cache off
+    #[serde(default = "default_cache_ttl_secs")]
+    cache_ttl_secs: u64,
+    /// Extra regexes for secrets to mask before anything is sent
+    #[serde(default)]
+    redact: RedactConfig,
+    /// Tell the model which optional tools (rg, fd, jq, ...) are installed
+    #[serde(default)]
+    tool_hints: bool,
+    /// Rough token budget shared by piped stdin and `--file` attachments
+    #[serde(default = "default_attachment_budget_tokens")]
+    attachment_budget_tokens: usize,
+    /// Context collectors used when `--context` isn't given, e.g. `["git", "tools"]`
+    #[serde(default)]
+    context: Vec<Collector>,
+    /// Keybindings for the refine prompt: "emacs" (default) or "vi"
+    #[serde(default)]
+    edit_mode: String,
+    /// Record generated commands in history.jsonl
+    #[serde(default = "default_save_history")]
+    save_history: bool,
+}
+
+fn default_max_fix_attempts() -> u32 {
+    3
+}
+
+fn default_cache_ttl_secs() -> u64 {
+    7 * 24 * 60 * 60
+}
+
+fn default_save_history() -> bool {
+    true
+}
+
+fn default_attachment_budget_tokens() -> usize {
+    8000
 }
 
 /// Returns path to config.json (XDG/AppData)
@@ -64,10 +251,10 @@ fn ensure_config_exists() -> Result<(), Box<dyn std::error::Error>> {
     if !path.exists() {
         let default = r#"{
   "models": { "gemini": "gemini-2.0-flash" },
-  "api_keys": { "GEMINI_API_KEY": "" },
+  "api_keys": { "GEMINI_API_KEY": "", "OPENAI_API_KEY": "", "ANTHROPIC_API_KEY": "" },
   "default_model": "gemini-2.0-flash",
   "streaming": true,
-  "system_prompt": "Answer in one sentence"
+  "system_prompt": ""
 }"#;
         fs::write(path, default)?;
     }
@@ -81,34 +268,122 @@ fn load_config() -> Result<Config, Box<dyn std::error::Error>> {
     Ok(cfg)
 }
 
-/// Syntax-highlight code for display
-fn print_highlighted_code(code: &str, ext: &str) -> Result<(), Box<dyn std::error::Error>> {
-    let ps = SyntaxSet::load_defaults_newlines();
-    let ts = ThemeSet::load_defaults();
-    let syntax = ps.find_syntax_by_extension(ext).ok_or("Unknown syntax")?;
-    let mut highlighter = HighlightLines::new(syntax, &ts.themes["base16-ocean.dark"]);
-    let width = terminal_size().map(|(w, _)| w.0 as usize).unwrap_or(80);
-    let max = width.saturating_sub(4);
-    println!("╭{}╮", "─".repeat(max + 2));
-    for line in code.lines() {
-        let mut buf = String::new();
-        let mut len = 0;
-        for word in line.split_whitespace() {
-            if len + word.len() + 1 <= max {
-                if !buf.is_empty() { buf.push(' '); len += 1; }
-                buf.push_str(word); len += word.len();
-            } else {
-                let ranges = highlighter.highlight_line(&buf, &ps)?;
-                println!("│ {}{} │", as_24_bit_terminal_escaped(&ranges, false), " ".repeat(max - buf.len()));
-                buf = word.to_string(); len = word.len();
-            }
-        }
-        let ranges = highlighter.highlight_line(&buf, &ps)?;
-        println!("│ {}{} │", as_24_bit_terminal_escaped(&ranges, false), " ".repeat(max - buf.len()));
-    }
-    println!("╰{}╯", "─".repeat(max + 2));
-    print!("\x1b[0m");
-    Ok(())
+/// Fill in the placeholders of a system prompt template
+fn render_system_prompt(template: &str, shell: Shell) -> String {
+    let template = match template.trim() {
+        "" | LEGACY_SYSTEM_PROMPT => DEFAULT_SYSTEM_PROMPT,
+        _ => template,
+    };
+    let cwd = env::current_dir().map(|p| p.display().to_string()).unwrap_or_default();
+    let user = env::var("USER").or_else(|_| env::var("USERNAME")).unwrap_or_default();
+    let date = chrono::Local::now().format("%Y-%m-%d").to_string();
+    template
+        .replace("{shell_notes}", shell.prompt_notes())
+        .replace("{shell}", shell.display_name())
+        .replace("{os}", env::consts::OS)
+        .replace("{arch}", env::consts::ARCH)
+        .replace("{cwd}", &cwd)
+        .replace("{user}", &user)
+        .replace("{date}", &date)
+        .trim()
+        .to_string()
+}
+
+/// The rendered system prompt followed by the project context, if any
+fn system_prompt(template: &str, shell: Shell, project_context: &str) -> String {
+    let prompt = render_system_prompt(template, shell);
+    if project_context.is_empty() {
+        prompt
+    } else {
+        format!("{prompt}\n\n{project_context}")
+    }
+}
+
+/// A model name or alias resolved to its target and provider
+fn resolve_target(name: &str, models: &HashMap<String, ModelAlias>) -> Result<(ModelTarget, AdapterKind), Box<dyn std::error::Error>> {
+    let target = resolve_model(name, models)?;
+    let kind = target.kind()?;
+    Ok((target, kind))
+}
+
+/// API key for a provider: the environment wins, then `api_keys` in the config
+fn resolve_api_key(kind: AdapterKind, api_keys: &HashMap<String, String>) -> Option<String> {
+    let env_name = kind.default_key_env_name()?;
+    env::var(env_name)
+        .ok()
+        .filter(|v| !v.is_empty())
+        .or_else(|| api_keys.get(env_name).filter(|v| !v.is_empty()).cloned())
+}
+
+/// Stream the model's answer into the preview box, returning the cleaned command
+async fn stream_answer(client: &Client, model: &str, chat_req: ChatRequest, preview: &mut LivePreview) -> Result<String, Box<dyn std::error::Error>> {
+    let chat_res = client.exec_chat_stream(model, chat_req, None).await?;
+    let mut stream = chat_res.stream;
+    let mut raw = String::new();
+    preview.update("")?;
+    while let Some(event) = stream.next().await {
+        if let ChatStreamEvent::Chunk(StreamChunk { content }) = event? {
+            raw.push_str(&content);
+            preview.update(&raw)?;
+        }
+    }
+    if raw.trim().is_empty() {
+        return Ok("NO ANSWER".to_string());
+    }
+    Ok(clean_answer(&raw))
+}
+
+/// Risk findings as preview notes
+fn risk_notes(findings: &[risk::Finding]) -> Vec<Note> {
+    findings
+        .iter()
+        .map(|f| match f.severity {
+            Severity::High => Note::new(NoteStyle::Danger, format!("High risk: {}", f.reason)),
+            Severity::Warning => Note::new(NoteStyle::Warning, format!("Caution: {}", f.reason)),
+        })
+        .collect()
+}
+
+/// Programs the command needs that aren't installed, as a preview note
+fn tool_notes(command: &str, shell: Shell) -> Vec<Note> {
+    let missing = tools::missing_tools(command, shell);
+    if missing.is_empty() {
+        return Vec::new();
+    }
+    vec![Note::new(NoteStyle::Warning, format!("Not found on PATH: {}", missing.join(", ")))]
+}
+
+/// Ask for a typed "yes" before running a high-risk command
+fn confirm_high_risk(input: &mut Input) -> bool {
+    println!("\x1b[1;31mThis command is flagged as high risk.\x1b[0m Type 'yes' to run it anyway.");
+    input.read_line("yes? ").is_some_and(|answer| answer.trim().eq_ignore_ascii_case("yes"))
+}
+
+/// Copy the command and say how, on stderr so `--print` output stays clean
+fn copy_to_clipboard(command: &str) {
+    match clipboard::copy(command) {
+        Ok(how) => eprintln!("Copied to the clipboard ({how})."),
+        Err(e) => eprintln!("Error: couldn't copy: {e}"),
+    }
+}
+
+/// Leave without running anything, putting the terminal back in order first
+fn cancel() -> ! {
+    println!("\x1b[0m");
+    eprintln!("Cancelled.");
+    exit(130);
+}
+
+/// Ctrl+C while aido itself is busy (waiting on or streaming from the model) cancels cleanly;
+/// while a command runs, the signal is left to the command and its status is reported as usual
+fn spawn_ctrl_c_handler() {
+    tokio::spawn(async {
+        while tokio::signal::ctrl_c().await.is_ok() {
+            if !run::child_running() {
+                cancel();
+            }
+        }
+    });
 }
 
 /// Strip out any ``` fences from the model’s output
@@ -129,84 +404,572 @@ fn clean_answer(raw: &str) -> String {
     s.trim().to_string()
 }
 
+/// Index of the first positional argument when it names a subcommand
+fn subcommand_position(args: &[OsString]) -> Option<usize> {
+    let cmd = Cli::command();
+    let takes_value = |flag: &str| {
+        cmd.get_arguments().any(|a| {
+            a.get_action().takes_values()
+                && (a.get_long().is_some_and(|l| flag == format!("--{l}")) || a.get_short().is_some_and(|s| flag == format!("-{s}")))
+        })
+    };
+    let mut i = 1;
+    while i < args.len() {
+        let arg = args[i].to_str()?;
+        if arg == "--" {
+            return None;
+        }
+        if arg.starts_with('-') && arg != "-" {
+            if !arg.contains('=') && takes_value(arg) {
+                i += 1;
+            }
+            i += 1;
+            continue;
+        }
+        return cmd.get_subcommands().any(|s| s.get_name() == arg).then_some(i);
+    }
+    None
+}
+
+/// Parse the command line; a prompt that merely starts with a subcommand's name
+/// (`aido fix the wifi driver`) is read as a prompt when it doesn't parse as that subcommand
+/// and nothing after the name looks like a flag, so mistyped options stay errors
+fn parse_cli_from(args: Vec<OsString>) -> Result<Cli, clap::Error> {
+    let err = match Cli::try_parse_from(&args) {
+        Ok(cli) => return Ok(cli),
+        Err(err) => err,
+    };
+    if matches!(err.kind(), ErrorKind::DisplayHelp | ErrorKind::DisplayVersion) {
+        return Err(err);
+    }
+    let Some(i) = subcommand_position(&args) else {
+        return Err(err);
+    };
+    if args[i + 1..].iter().any(|a| a.to_string_lossy().starts_with('-')) {
+        return Err(err);
+    }
+    let mut retry = args;
+    retry.insert(i, "--".into());
+    Cli::try_parse_from(retry).map_err(|_| err)
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // 1) ensure API key
-    if env::var("GEMINI_API_KEY").ok().filter(|v| !v.is_empty()).is_none() {
-        eprintln!("Error: GEMINI_API_KEY not set. Please set it in your environment.");
-        exit(1);
+    // 1) parse args + load config
+    let cli = parse_cli_from(env::args_os().collect()).unwrap_or_else(|e| e.exit());
+    if let Some(Commands::Init { target }) = cli.command {
+        match init::script(target) {
+            Ok(script) => print!("{script}"),
+            Err(e) => {
+                eprintln!("Error: {e}");
+                exit(1);
+            }
+        }
+        return Ok(());
     }
-
-    // 2) parse args + load config
-    let cli = Cli::parse();
     let prompt = cli.prompt.join(" ");
     ensure_config_exists()?;
     let cfg = load_config()?;
-    let model = cli.model.unwrap_or(cfg.default_model);
+    if let Some(Commands::Models) = cli.command {
+        return models::print_models(&cfg.models, &cfg.default_model);
+    }
 
-    // 3) prepare LLM client and initial message history
-    let client = Client::default();
-    let mut messages = {
-        let shell_name = match cli.shell { Shell::Bash => "bash", Shell::PowerShell => "PowerShell" };
-        vec![
-            ChatMessage::system(format!(
-                "Give a {} one-liner to answer the question. The command will run on {} {}. Do not use a code block or backticks.",
-                shell_name,
-                env::consts::OS,
-                env::consts::ARCH
-            )),
-            ChatMessage::user(prompt.clone()),
-        ]
+    // `history run` replays a stored command instead of asking the model for one
+    let replay = match &cli.command {
+        Some(Commands::History(args)) => match args.action {
+            None => return history::print_history(args),
+            Some(history::HistoryAction::Run { id }) => Some(history::find(id).unwrap_or_else(|e| {
+                eprintln!("Error: {e}");
+                exit(1);
+            })),
+        },
+        _ => None,
+    };
+    let model_name = cli.model.as_deref().or(replay.as_ref().map(|e| e.model.as_str())).unwrap_or(&cfg.default_model);
+    let target = resolve_model(model_name, &cfg.models)?;
+    let mut model = target.model.clone();
+    let mut shell = cli
+        .shell
+        .or_else(|| replay.as_ref().and_then(|e| shell_from_name(&e.shell)))
+        .unwrap_or_else(|| detect_shell(&ShellEnv::current(cfg.default_shell.clone())));
+
+    // find the interpreter before spending an API call on a command we can't run
+    let mode = Mode::from_cli(&cli);
+    let mut interpreter = if cli.copy || matches!(mode, Mode::Print | Mode::DryRun) {
+        None
+    } else {
+        match shell.find_interpreter(&cfg.shell_paths) {
+            Ok(path) => Some(path),
+            Err(e) => {
+                eprintln!("Error: {e}");
+                exit(1);
+            }
+        }
     };
 
+    // the first user message: the prompt, or a request to repair a failed command
+    let request = match &cli.command {
+        Some(Commands::Fix(args)) => fix::fix_prompt(args, shell).unwrap_or_else(|e| {
+            eprintln!("Error: {e}");
+            exit(1);
+        }),
+        _ => match &replay {
+            Some(entry) => entry.prompt.clone(),
+            None => prompt,
+        },
+    };
+    // piped data and --file contents ride along with the first message (a replay needs neither)
+    let mut attachments = Vec::new();
+    // once piped stdin is read, the command gets the terminal instead of an exhausted pipe
+    let stdin_consumed = replay.is_none() && !cli.no_stdin && attach::stdin_carries_data();
+    if replay.is_none() {
+        let budget = cfg.attachment_budget_tokens;
+        let stdin = if stdin_consumed { attach::read_stdin(budget) } else { Ok(None) };
+        let files = cli.files.iter().map(|path| attach::read_file(path, budget).map(Some));
+        for attachment in std::iter::once(stdin).chain(files) {
+            match attachment {
+                Ok(Some(a)) => attachments.push(a),
+                Ok(None) => {}
+                Err(e) => {
+                    eprintln!("Error: {e}");
+                    exit(1);
+                }
+            }
+        }
+    }
+    let mut draft = history::Draft {
+        enabled: cfg.save_history,
+        prompt: request.clone(),
+        refinements: Vec::new(),
+        model: model.clone(),
+        shell: shell.cli_name(),
+    };
+
+    // 2) ensure an API key for the selected provider (a replay may never need one)
+    let kind = target.kind()?;
+    if let (Some(env_name), None) = (kind.default_key_env_name(), &replay) {
+        if resolve_api_key(kind, &cfg.api_keys).is_none() {
+            eprintln!(
+                "Error: no API key for {kind} (model '{model}'). Set {env_name} in your environment or under \"api_keys\" in {}.",
+                get_config_path().display()
+            );
+            exit(1);
+        }
+    }
+
+    // 3) prepare LLM client and initial message history
+    let api_keys = cfg.api_keys.clone();
+    let auth_resolver = AuthResolver::from_resolver_fn(
+        move |model_iden: ModelIden| -> Result<Option<AuthData>, genai::resolver::Error> {
+            Ok(resolve_api_key(model_iden.adapter_kind, &api_keys).map(AuthData::from_single))
+        },
+    );
+    // the alias picked with --model or /model decides provider and endpoint, over genai's guess
+    // from the model name; looked up per request since several aliases may share a model ID
+    let served = Arc::new(Mutex::new(target.clone()));
+    let mapper_target = Arc::clone(&served);
+    let model_mapper = ModelMapper::from_mapper_fn(
+        move |model_iden: ModelIden| -> Result<ModelIden, genai::resolver::Error> {
+            match mapper_target.lock().unwrap().provider {
+                Some(kind) => Ok(ModelIden::new(kind, model_iden.model_name)),
+                None => Ok(model_iden),
+            }
+        },
+    );
+    let endpoint_target = Arc::clone(&served);
+    let target_resolver = ServiceTargetResolver::from_resolver_fn(
+        move |service_target: ServiceTarget| -> Result<ServiceTarget, genai::resolver::Error> {
+            match endpoint_target.lock().unwrap().endpoint.clone() {
+                Some(url) => Ok(ServiceTarget { endpoint: Endpoint::from_owned(url), ..service_target }),
+                None => Ok(service_target),
+            }
+        },
+    );
+    let client = Client::builder()
+        .with_auth_resolver(auth_resolver)
+        .with_model_mapper(model_mapper)
+        .with_service_target_resolver(target_resolver)
+        .build();
+    let collectors = if cli.context.is_empty() { &cfg.context } else { &cli.context };
+    let mut project_context = context::gather(collectors);
+    if cfg.tool_hints {
+        if !project_context.is_empty() {
+            project_context.push_str("\n\n");
+        }
+        project_context.push_str(&tools::tool_hints());
+    }
+    let mut messages = vec![
+        ChatMessage::system(system_prompt(&cfg.system_prompt, shell, &project_context)),
+        ChatMessage::user(attach::with_attachments(&request, &attachments, cfg.attachment_budget_tokens)),
+    ];
+    let analyzer = RiskAnalyzer::new(&cfg.risk)?;
+    let redactor = Redactor::new(&cfg.redact, &cfg.api_keys, cli.show_redactions)?;
+    let mut fix_attempts = 0;
+    // an answer to show instead of asking the model: a replayed or picked command
+    let mut preset = replay.map(|e| e.command);
+    let cache = Cache::new(cfg.cache_ttl_secs);
+    let mut candidate_count = if mode == Mode::Interactive { usize::from(cli.candidates) } else { 1 };
+    let mut force_fresh = false;
+    let mut syntax_retried = false;
+    // key of the request whose answer didn't parse, so its correction is cached in its place
+    let mut uncorrected_key = None;
+    let mut input = Input::new(
+        &cfg.edit_mode,
+        cfg.models.keys().cloned().collect(),
+        Shell::value_variants()
+            .iter()
+            .filter_map(|shell| shell.to_possible_value())
+            .flat_map(|value| value.get_name_and_aliases().map(String::from).collect::<Vec<_>>())
+            .collect(),
+    )?;
+    spawn_ctrl_c_handler();
+
     // 4) interactive preview → refine → accept loop
     loop {
-        let chat_req = ChatRequest::new(messages.clone());
-        let chat_res = client.exec_chat(&model, chat_req, None).await?;
-        let raw = chat_res.content_text_as_str().unwrap_or("NO ANSWER");
-        let answer = clean_answer(raw);
+        let chat_req = ChatRequest::new(redactor.messages(&messages));
+        let ext = shell.ext();
 
-        // show highlighted preview
-        let ext = if matches!(cli.shell, Shell::PowerShell) { "ps1" } else { "sh" };
-        print_highlighted_code(&answer, ext)
-            .unwrap_or_else(|_| println!("{answer}"));
+        // several alternatives: show each in its own box and let the user choose
+        if candidate_count > 1 && preset.is_none() {
+            let candidates = candidates::generate(&client, &model, &redactor.messages(&messages), candidate_count).await?;
+            for (i, candidate) in candidates.iter().enumerate() {
+                println!("\x1b[1m[{}]\x1b[0m", i + 1);
+                let mut notes = risk_notes(&analyzer.analyze(candidate));
+                notes.extend(tool_notes(candidate, shell));
+                print_highlighted_code(candidate, ext, &notes)
+                    .unwrap_or_else(|_| println!("{candidate}"));
+            }
+            let Some(choice) = candidates::read_choice(&mut input, candidates.len()) else { cancel() };
+            match choice {
+                candidates::Choice::Pick(i) => {
+                    candidate_count = 1;
+                    preset = Some(candidates[i].clone());
+                }
+                candidates::Choice::Refine(i, text) => {
+                    candidate_count = 1;
+                    messages.push(ChatMessage::assistant(candidates[i].clone()));
+                    messages.push(ChatMessage::user(text.clone()));
+                    draft.refinements.push(text);
+                }
+                candidates::Choice::RefineAll(text) => {
+                    messages.push(ChatMessage::assistant(candidates.join("\n")));
+                    messages.push(ChatMessage::user(text.clone()));
+                    draft.refinements.push(text);
+                }
+                candidates::Choice::Regenerate => {}
+            }
+            continue;
+        }
+
+        // show highlighted preview, live when streaming
+        let mut live = (cfg.streaming && mode != Mode::Print).then(|| LivePreview::new(ext));
+        let mut notes = Vec::new();
+        let lookup_key = Cache::key(&served.lock().unwrap(), &shell.cli_name(), &messages);
+        let cached = cache.as_ref().filter(|_| !cli.no_cache && !force_fresh).and_then(|c| c.get(&lookup_key));
+        let cache_key = uncorrected_key.take().unwrap_or(lookup_key);
+        force_fresh = false;
+        // replayed, picked and hand-edited commands are the user's; don't send those back for correction
+        let from_model = preset.is_none();
+        let mut fresh = false;
+        let answer = match (preset.take(), cached) {
+            (Some(command), _) => command,
+            (None, Some((answer, age))) => {
+                notes.push(Note::new(NoteStyle::Info, format!("Cached answer from {} ago; --no-cache asks the model again", format_age(age))));
+                answer
+            }
+            (None, None) => {
+                let answer = match live.as_mut() {
+                    Some(preview) => stream_answer(&client, &model, chat_req, preview).await?,
+                    None => {
+                        let chat_res = client.exec_chat(&model, chat_req, None).await?;
+                        clean_answer(chat_res.content_text_as_str().unwrap_or("NO ANSWER"))
+                    }
+                };
+                fresh = true;
+                answer
+            }
+        };
+
+        // flag anything dangerous inside the box
+        let findings = analyzer.analyze(&answer);
+        let high_risk = findings.iter().any(|f| f.severity == Severity::High);
+        notes.extend(risk_notes(&findings));
+        notes.extend(tool_notes(&answer, shell));
+
+        // parse it before anyone runs it; an answer that doesn't parse gets one correction round
+        let syntax_error = syntax::check(shell, &cfg.shell_paths, &answer);
+        if let Some(error) = &syntax_error {
+            notes.push(Note::new(NoteStyle::Danger, format!("Syntax error: {error}")));
+            if from_model && !syntax_retried {
+                syntax_retried = true;
+                if let Some(preview) = live.as_mut() {
+                    preview.redraw(&answer, &notes).ok();
+                }
+                eprintln!("aido: that doesn't parse, asking for a correction…");
+                uncorrected_key = Some(cache_key);
+                messages.push(ChatMessage::assistant(answer.clone()));
+                messages.push(ChatMessage::user(format!(
+                    "That command doesn't parse as {}: {error}\nReply with only the corrected command, no explanation.",
+                    shell.display_name()
+                )));
+                continue;
+            }
+        }
+        syntax_retried = false;
+        // only answers that parse are worth keeping
+        if let (Some(cache), true, None) = (&cache, fresh && answer != "NO ANSWER", &syntax_error) {
+            cache.put(&cache_key, &answer);
+        }
 
-        // prompt for refinement
-        println!("Type to refine, Enter to accept, Ctrl+C to bail");
-        print!("> ");
-        io::stdout().flush().unwrap();
+        if mode == Mode::Print {
+            for note in &notes {
+                eprintln!("aido: {}", note.text);
+            }
+            println!("{answer}");
+            if cli.copy {
+                copy_to_clipboard(&answer);
+            }
+            draft.record(&answer, None);
+            return Ok(());
+        }
+        match live.as_mut() {
+            Some(preview) => preview.redraw(&answer, &notes),
+            None => print_highlighted_code(&answer, ext, &notes),
+        }
+        .unwrap_or_else(|_| println!("{answer}"));
+        if cli.explain {
+            explain::print_explanation(&explain::explain(&client, &model, shell, &redactor, &answer).await?);
+        }
+        if mode == Mode::DryRun {
+            if cli.copy {
+                copy_to_clipboard(&answer);
+            }
+            draft.record(&answer, None);
+            return Ok(());
+        }
 
-        let mut input = String::new();
-        io::stdin().read_line(&mut input).unwrap();
+        loop {
+            if mode == Mode::Interactive {
+                // prompt for refinement
+                println!("Type to refine, Enter to accept, /help for commands, Ctrl+C to bail");
+                let Some(line) = input.read_line("> ") else { cancel() };
+                let line = line.trim().to_string();
+                let slash = match line.as_str() {
+                    "?" => Some(Ok(Slash::Explain)),
+                    ":e" => Some(Ok(Slash::Edit)),
+                    _ => parse_slash(&line),
+                };
+                match slash {
+                    Some(Err(e)) => {
+                        eprintln!("{e}");
+                        continue;
+                    }
+                    Some(Ok(Slash::Explain)) => {
+                        explain::print_explanation(&explain::explain(&client, &model, shell, &redactor, &answer).await?);
+                        continue;
+                    }
+                    Some(Ok(Slash::Edit)) => {
+                        let edited = match editor::edit(&answer, ext) {
+                            Ok(edited) => edited,
+                            Err(e) => {
+                                eprintln!("Error: {e}");
+                                continue;
+                            }
+                        };
+                        if edited.is_empty() || edited == answer {
+                            continue;
+                        }
+                        // keep the conversation in step so later refinements build on the edit
+                        messages.push(ChatMessage::assistant(answer.clone()));
+                        messages.push(ChatMessage::user(format!(
+                            "I edited the command by hand to:\n{edited}\nTreat this as the current command from now on."
+                        )));
+                        draft.refinements.push(format!("edited: {edited}"));
+                        preset = Some(edited);
+                        break;
+                    }
+                    Some(Ok(Slash::Copy)) => {
+                        copy_to_clipboard(&answer);
+                        continue;
+                    }
+                    Some(Ok(Slash::Regen)) => {
+                        force_fresh = true;
+                        break;
+                    }
+                    Some(Ok(Slash::Model(name))) => {
+                        let (target, kind) = match resolve_target(&name, &cfg.models) {
+                            Ok(resolved) => resolved,
+                            Err(e) => {
+                                eprintln!("Error: {e}");
+                                continue;
+                            }
+                        };
+                        model = target.model.clone();
+                        *served.lock().unwrap() = target;
+                        if let Some(env_name) = kind.default_key_env_name() {
+                            if resolve_api_key(kind, &cfg.api_keys).is_none() {
+                                eprintln!("Warning: no API key for {kind}; set {env_name} before asking this model.");
+                            }
+                        }
+                        println!("Model: {model}");
+                        draft.model = model.clone();
+                        break;
+                    }
+                    Some(Ok(Slash::Shell(name))) => {
+                        let Some(new_shell) = shell_from_name(&name) else {
+                            eprintln!("Unknown shell '{name}'");
+                            continue;
+                        };
+                        if interpreter.is_some() {
+                            match new_shell.find_interpreter(&cfg.shell_paths) {
+                                Ok(path) => interpreter = Some(path),
+                                Err(e) => {
+                                    eprintln!("Error: {e}");
+                                    continue;
+                                }
+                            }
+                        }
+                        shell = new_shell;
+                        messages[0] = ChatMessage::system(system_prompt(&cfg.system_prompt, shell, &project_context));
+                        draft.shell = shell.cli_name();
+                        println!("Shell: {}", shell.display_name());
+                        break;
+                    }
+                    Some(Ok(Slash::Help)) => {
+                        for (name, help) in SLASH_COMMANDS {
+                            println!("  {name:<9} {help}");
+                        }
+                        println!("  ? and :e are shortcuts for /explain and /edit");
+                        continue;
+                    }
+                    Some(Ok(Slash::Quit)) => return Ok(()),
+                    None if !line.is_empty() => {
+                        // refine and ask the model again
+                        messages.push(ChatMessage::assistant(answer.clone()));
+                        messages.push(ChatMessage::user(line.clone()));
+                        draft.refinements.push(line);
+                        break;
+                    }
+                    None => {}
+                }
+            }
 
-        if input.trim().is_empty() {
             // accepted!
-            if cli.dry_run {
-                println!("{answer}");
+            if cli.copy {
+                copy_to_clipboard(&answer);
+                draft.record(&answer, None);
                 return Ok(());
             }
+            if high_risk {
+                if !terminal_available() {
+                    eprintln!("Error: refusing to run a high-risk command without confirmation.");
+                    exit(1);
+                }
+                if !confirm_high_risk(&mut input) {
+                    println!("Not running it.");
+                    if mode == Mode::Yes {
+                        exit(1);
+                    }
+                    continue;
+                }
+            }
 
-            // execute in the chosen shell
-            let mut cmd = match cli.shell {
-                Shell::Bash => {
-                    let mut c = Command::new("bash");
-                    c.arg("-c").arg(&answer);
-                    c
+            if syntax_error.is_some() && mode == Mode::Yes {
+                eprintln!("Error: not running a command that doesn't parse.");
+                exit(1);
+            }
+            if mode == Mode::Yes {
+                if let Some(program) = tools::missing_first_program(&answer, shell) {
+                    eprintln!("Error: not running a command that starts with `{program}`, which isn't a builtin or on PATH.");
+                    exit(1);
                 }
-                Shell::PowerShell => {
-                    let mut c = Command::new("powershell");
-                    c.arg("-NoProfile").arg("-Command").arg(&answer);
-                    c
+            }
+
+            // execute in the chosen shell
+            let interpreter = interpreter.as_deref().ok_or("no interpreter resolved")?;
+            let mut cmd = shell.command(interpreter, &answer);
+            if stdin_consumed {
+                if let Ok(tty) = fs::File::open("/dev/tty") {
+                    cmd.stdin(tty);
                 }
-            };
-            let status = cmd.spawn()?.wait()?;
-            if !status.success() {
-                eprintln!("Command failed with status: {}", status.code().unwrap_or(1));
             }
-            return Ok(());
+            let outcome = run::run(cmd, cli.capture.as_deref(), cli.fix)?;
+            let code = run::exit_code(outcome.status);
+            draft.record(&answer, Some(code));
+            if outcome.status.success() {
+                return Ok(());
+            }
+            eprintln!("Command failed with status: {code}");
+            if !cli.fix || fix_attempts >= cfg.max_fix_attempts {
+                exit(code);
+            }
+
+            // feed the failure back and preview the correction
+            fix_attempts += 1;
+            println!("Asking for a fix (attempt {fix_attempts}/{})…", cfg.max_fix_attempts);
+            let stderr = outcome.stderr.trim();
+            messages.push(ChatMessage::assistant(answer.clone()));
+            messages.push(ChatMessage::user(format!(
+                "That command failed with exit code {code}. Its stderr was:\n{}\nGive a corrected command.",
+                if stderr.is_empty() { "(empty)" } else { stderr }
+            )));
+            break;
         }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(line: &str) -> Cli {
+        let args = std::iter::once("aido").chain(line.split_whitespace()).map(OsString::from).collect();
+        parse_cli_from(args).unwrap_or_else(|e| panic!("{line}: {e}"))
+    }
 
-        // otherwise, refine and loop again
-        messages.push(ChatMessage::user(input.trim().to_string()));
+    fn prompt_of(args: &str) -> String {
+        let cli = parse(args);
+        assert!(cli.command.is_none(), "{args} parsed as a subcommand");
+        cli.prompt.join(" ")
+    }
+
+    #[test]
+    fn prompts_may_start_with_subcommand_names() {
+        assert_eq!(prompt_of("fix the wifi driver"), "fix the wifi driver");
+        assert_eq!(prompt_of("--shell zsh fix the wifi driver"), "fix the wifi driver");
+        assert_eq!(prompt_of("init a git repo"), "init a git repo");
+        assert_eq!(prompt_of("history of commands containing ssh"), "history of commands containing ssh");
+        assert_eq!(prompt_of("history run the tests"), "history run the tests");
+        assert_eq!(prompt_of("models in this dir sorted by size"), "models in this dir sorted by size");
+        assert_eq!(prompt_of("help me find big files"), "help me find big files");
+    }
+
+    #[test]
+    fn subcommands_still_parse() {
+        assert!(matches!(parse("fix --status 1").command, Some(Commands::Fix(_))));
+        assert!(matches!(parse("init zsh").command, Some(Commands::Init { target: Shell::Zsh })));
+        assert!(matches!(parse("history --search ssh").command, Some(Commands::History(_))));
+        assert!(matches!(parse("history run 3").command, Some(Commands::History(_))));
+        assert!(matches!(parse("models").command, Some(Commands::Models)));
+    }
+
+    #[test]
+    fn renders_system_prompt_placeholders() {
+        let default = render_system_prompt("", Shell::Fish);
+        assert!(default.starts_with("Give a fish one-liner"), "{default}");
+        assert!(default.contains(env::consts::OS) && default.contains(env::consts::ARCH) && !default.contains('{'), "{default}");
+        assert_eq!(render_system_prompt(LEGACY_SYSTEM_PROMPT, Shell::Fish), default);
+        let custom = render_system_prompt("  {shell} in {cwd} on {date} ", Shell::Bash);
+        let cwd = env::current_dir().unwrap().display().to_string();
+        let date = chrono::Local::now().format("%Y-%m-%d").to_string();
+        assert_eq!(custom, format!("bash in {cwd} on {date}"));
+    }
+
+    #[test]
+    fn mistyped_subcommand_options_stay_errors() {
+        for line in ["history --serach ssh", "fix --stauts 1", "fix --status", "init zsh --bogus"] {
+            let args = std::iter::once("aido").chain(line.split_whitespace()).map(OsString::from).collect();
+            assert!(parse_cli_from(args).is_err(), "{line} parsed");
+        }
     }
 }
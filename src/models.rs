@@ -0,0 +1,126 @@
+use genai::adapter::AdapterKind;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// An entry of `models` in the config: a bare model ID, or a model with routing
+#[derive(Deserialize, Clone)]
+#[serde(untagged)]
+pub enum ModelAlias {
+    Id(String),
+    Target {
+        model: String,
+        /// Provider to route to instead of the one guessed from the model name
+        #[serde(default)]
+        provider: Option<String>,
+        /// Base URL of an OpenAI/Ollama-compatible server
+        #[serde(default)]
+        endpoint: Option<String>,
+    },
+}
+
+/// Where a `--model` value ends up after alias resolution
+#[derive(Clone)]
+pub struct ModelTarget {
+    pub model: String,
+    pub provider: Option<AdapterKind>,
+    pub endpoint: Option<String>,
+}
+
+impl ModelTarget {
+    /// Provider that will serve the request
+    pub fn kind(&self) -> Result<AdapterKind, Box<dyn std::error::Error>> {
+        match self.provider {
+            Some(kind) => Ok(kind),
+            None => Ok(AdapterKind::from_model(&self.model)?),
+        }
+    }
+}
+
+/// Every provider aido can route to
+pub const PROVIDERS: &[AdapterKind] = &[
+    AdapterKind::OpenAI,
+    AdapterKind::Ollama,
+    AdapterKind::Anthropic,
+    AdapterKind::Cohere,
+    AdapterKind::Gemini,
+    AdapterKind::Groq,
+    AdapterKind::Xai,
+    AdapterKind::DeepSeek,
+];
+
+/// Map a provider name from the config to a genai adapter
+fn parse_provider(name: &str) -> Result<AdapterKind, String> {
+    match name.to_lowercase().as_str() {
+        "openai" => Ok(AdapterKind::OpenAI),
+        "ollama" => Ok(AdapterKind::Ollama),
+        "anthropic" => Ok(AdapterKind::Anthropic),
+        "cohere" => Ok(AdapterKind::Cohere),
+        "gemini" => Ok(AdapterKind::Gemini),
+        "groq" => Ok(AdapterKind::Groq),
+        "xai" => Ok(AdapterKind::Xai),
+        "deepseek" => Ok(AdapterKind::DeepSeek),
+        other => Err(format!("Unknown provider '{other}'")),
+    }
+}
+
+/// Resolve an alias (or a plain model ID) to its target
+pub fn resolve_model(name: &str, models: &HashMap<String, ModelAlias>) -> Result<ModelTarget, String> {
+    match models.get(name) {
+        None => Ok(ModelTarget { model: name.to_string(), provider: None, endpoint: None }),
+        Some(ModelAlias::Id(model)) => Ok(ModelTarget { model: model.clone(), provider: None, endpoint: None }),
+        Some(ModelAlias::Target { model, provider, endpoint }) => Ok(ModelTarget {
+            model: model.clone(),
+            provider: provider.as_deref().map(parse_provider).transpose()?,
+            endpoint: endpoint.clone(),
+        }),
+    }
+}
+
+/// Print configured aliases with their resolved targets (`aido models`)
+pub fn print_models(models: &HashMap<String, ModelAlias>, default_model: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let mut aliases: Vec<_> = models.keys().collect();
+    aliases.sort();
+    let width = aliases.iter().map(|a| a.len()).max().unwrap_or(0);
+    if aliases.is_empty() {
+        println!("No model aliases configured.");
+    }
+    for alias in aliases {
+        let target = resolve_model(alias, models)?;
+        let marker = if alias == default_model || target.model == default_model { "*" } else { " " };
+        let mut line = format!("{marker} {alias:<width$}  → {} ({})", target.model, target.kind()?);
+        if let Some(endpoint) = &target.endpoint {
+            line.push_str(&format!(" @ {endpoint}"));
+        }
+        println!("{line}");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_aliases_to_their_own_targets() {
+        let alias = |endpoint: &str| ModelAlias::Target {
+            model: "llama3".to_string(),
+            provider: Some("ollama".to_string()),
+            endpoint: Some(endpoint.to_string()),
+        };
+        let models = HashMap::from([
+            ("box".to_string(), alias("http://box:11434/")),
+            ("laptop".to_string(), alias("http://localhost:11434/")),
+            ("fast".to_string(), ModelAlias::Id("gpt-4o-mini".to_string())),
+            ("typo".to_string(), ModelAlias::Target { model: "x".to_string(), provider: Some("nope".to_string()), endpoint: None }),
+        ]);
+        let box_target = resolve_model("box", &models).unwrap();
+        assert_eq!((box_target.model.as_str(), box_target.provider), ("llama3", Some(AdapterKind::Ollama)));
+        assert_eq!(box_target.endpoint.as_deref(), Some("http://box:11434/"));
+        assert_eq!(resolve_model("laptop", &models).unwrap().endpoint.as_deref(), Some("http://localhost:11434/"));
+        let fast = resolve_model("fast", &models).unwrap();
+        assert_eq!(fast.model, "gpt-4o-mini");
+        assert!(fast.provider.is_none() && fast.endpoint.is_none());
+        assert_eq!(resolve_model("gpt-4o", &models).unwrap().model, "gpt-4o");
+        assert!(resolve_model("typo", &models).is_err());
+    }
+}
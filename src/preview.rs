@@ -0,0 +1,191 @@
+use std::io::{self, Write};
+use syntect::easy::HighlightLines;
+use syntect::highlighting::ThemeSet;
+use syntect::parsing::SyntaxSet;
+use syntect::util::as_24_bit_terminal_escaped;
+use terminal_size::terminal_size;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// How a note under the command is coloured
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum NoteStyle {
+    Info,
+    Warning,
+    Danger,
+}
+
+/// A line shown under the command inside the preview box
+pub struct Note {
+    pub style: NoteStyle,
+    pub text: String,
+}
+
+impl Note {
+    pub fn new(style: NoteStyle, text: impl Into<String>) -> Self {
+        Note { style, text: text.into() }
+    }
+}
+
+/// Usable width inside the preview box, and the terminal's height
+fn box_size() -> (usize, usize) {
+    let (width, height) = terminal_size().map_or((80, 24), |(w, h)| (w.0 as usize, h.0 as usize));
+    (width.saturating_sub(4).max(10), height)
+}
+
+/// Columns `text` takes up on screen
+fn display_width(text: &str) -> usize {
+    UnicodeWidthStr::width(text)
+}
+
+/// Word-wrap `text` into rows at most `max` columns wide, hard-breaking words that are wider
+fn wrap(text: &str, max: usize) -> Vec<String> {
+    let mut rows = Vec::new();
+    let mut row = String::new();
+    let mut len = 0;
+    for word in text.split_whitespace() {
+        let wlen = display_width(word);
+        if len > 0 && len + 1 + wlen <= max {
+            row.push(' ');
+            row.push_str(word);
+            len += 1 + wlen;
+            continue;
+        }
+        if len > 0 {
+            rows.push(std::mem::take(&mut row));
+            len = 0;
+        }
+        for c in word.chars() {
+            let cw = UnicodeWidthChar::width(c).unwrap_or(0);
+            if len + cw > max {
+                rows.push(std::mem::take(&mut row));
+                len = 0;
+            }
+            row.push(c);
+            len += cw;
+        }
+    }
+    rows.push(row);
+    rows
+}
+
+/// Wrap and highlight code into the bordered rows of the preview box
+fn boxed_lines(code: &str, ext: &str, ps: &SyntaxSet, ts: &ThemeSet, max: usize) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let syntax = ps.find_syntax_by_extension(ext).unwrap_or_else(|| ps.find_syntax_plain_text());
+    let mut highlighter = HighlightLines::new(syntax, &ts.themes["base16-ocean.dark"]);
+    let mut rows = Vec::new();
+    for line in code.lines() {
+        for row in wrap(line, max) {
+            let ranges = highlighter.highlight_line(&row, ps)?;
+            rows.push(format!("│ {}\x1b[0m{} │", as_24_bit_terminal_escaped(&ranges, false), " ".repeat(max.saturating_sub(display_width(&row)))));
+        }
+    }
+    Ok(rows)
+}
+
+/// Word-wrap notes into coloured rows below a divider
+fn note_rows(notes: &[Note], max: usize) -> Vec<String> {
+    if notes.is_empty() {
+        return Vec::new();
+    }
+    let mut rows = vec![format!("├{}┤", "─".repeat(max + 2))];
+    for note in notes {
+        let (color, icon) = match note.style {
+            NoteStyle::Info => ("\x1b[36m", "•"),
+            NoteStyle::Warning => ("\x1b[33m", "⚠"),
+            NoteStyle::Danger => ("\x1b[1;31m", "⚠"),
+        };
+        // the icon and its space take two columns; continuation rows are indented to match
+        for (i, text) in wrap(&note.text, max.saturating_sub(2).max(1)).into_iter().enumerate() {
+            let line = format!("{} {text}", if i == 0 { icon } else { " " });
+            let len = display_width(&line);
+            rows.push(format!("│ {color}{line}\x1b[0m{} │", " ".repeat(max.saturating_sub(len))));
+        }
+    }
+    rows
+}
+
+/// Syntax-highlight code for display, with optional notes underneath
+pub fn print_highlighted_code(code: &str, ext: &str, notes: &[Note]) -> Result<(), Box<dyn std::error::Error>> {
+    LivePreview::new(ext).redraw(code, notes)
+}
+
+/// Preview box that can be redrawn in place, e.g. while an answer streams in
+pub struct LivePreview {
+    ps: SyntaxSet,
+    ts: ThemeSet,
+    ext: String,
+    max: usize,
+    /// Terminal rows; a box taller than this can't be rewound
+    height: usize,
+    /// Rows printed by the last redraw
+    drawn: usize,
+}
+
+impl LivePreview {
+    /// Nothing is printed until the first redraw
+    pub fn new(ext: &str) -> Self {
+        let (max, height) = box_size();
+        LivePreview {
+            ps: SyntaxSet::load_defaults_newlines(),
+            ts: ThemeSet::load_defaults(),
+            ext: ext.to_string(),
+            max,
+            height,
+            drawn: 0,
+        }
+    }
+
+    fn rows(&self, text: &str, notes: &[Note]) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let mut rows = boxed_lines(text, &self.ext, &self.ps, &self.ts, self.max)?;
+        rows.extend(note_rows(notes, self.max));
+        Ok(rows)
+    }
+
+    /// Show partial text while it streams in; once the box would no longer fit on screen
+    /// the last box that did stays put until the final `redraw`
+    pub fn update(&mut self, text: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let rows = self.rows(text, &[])?;
+        if rows.len() + 2 >= self.height {
+            return Ok(());
+        }
+        self.draw(&rows)
+    }
+
+    /// Replace the whole box with a fresh highlight of `text`
+    pub fn redraw(&mut self, text: &str, notes: &[Note]) -> Result<(), Box<dyn std::error::Error>> {
+        let rows = self.rows(text, notes)?;
+        self.draw(&rows)
+    }
+
+    fn draw(&mut self, rows: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+        let mut out = io::stdout().lock();
+        // only a box that fit on screen can be rewound; a taller one stays and the new box goes below
+        if self.drawn > 0 && self.drawn < self.height {
+            // move back up to the top border and clear everything below it
+            write!(out, "\x1b[{}A\x1b[J", self.drawn)?;
+        }
+        writeln!(out, "╭{}╮", "─".repeat(self.max + 2))?;
+        for row in rows {
+            writeln!(out, "{row}")?;
+        }
+        writeln!(out, "╰{}╯\x1b[0m", "─".repeat(self.max + 2))?;
+        out.flush()?;
+        self.drawn = rows.len() + 2;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wraps_to_display_width() {
+        assert_eq!(wrap("ls -la /tmp", 20), ["ls -la /tmp"]);
+        assert_eq!(wrap("echo aaaaaaaaaaaa", 5), ["echo", "aaaaa", "aaaaa", "aa"]);
+        // wide characters take two columns each
+        assert_eq!(wrap("echo 日本語です", 6), ["echo", "日本語", "です"]);
+        assert!(wrap("grep -r 'ünïcödé' .", 12).iter().all(|row| display_width(row) <= 12));
+        assert_eq!(wrap("", 10), [""]);
+    }
+}
@@ -0,0 +1,200 @@
+use regex::Regex;
+use serde::Deserialize;
+
+/// How worried to be about a command
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Severity {
+    /// Shown in the preview, Enter still runs it
+    Warning,
+    /// Needs an explicit typed confirmation
+    High,
+}
+
+/// One reason a command looks dangerous
+pub struct Finding {
+    pub severity: Severity,
+    pub reason: String,
+}
+
+/// `risk` section of the config
+#[derive(Deserialize, Default)]
+pub struct RiskConfig {
+    /// Regexes for commands that are known to be fine; built-in rules are skipped for them
+    #[serde(default)]
+    pub allow: Vec<String>,
+    /// Regexes for commands that always need confirmation
+    #[serde(default)]
+    pub deny: Vec<String>,
+}
+
+/// `rm` with a recursive flag, short (`-rf`) or long (`--recursive`)
+const RM_RECURSIVE: &str = r"\brm\b[^;&|]*\s(?:-[a-zA-Z]*[rR][a-zA-Z]*|--recursive)(?:\s|$)";
+
+/// `rm` of `/`, a `/*` glob, anything under a top-level system directory, or the home directory
+const RM_CRITICAL_TARGET: &str = r#"\brm\b[^;&|]*\s["']?(?:/(?:\*[^\s;&|'"]*|(?:etc|usr|bin|sbin|boot|lib|lib64|var|home|opt|root|srv)\b[^\s;&|'"]*)?|(?:~|\$HOME|\$\{HOME\})["']?(?:/\*?)?)["']?(?:\s|$|[;&|])"#;
+
+/// Built-in rules: every pattern must match for the rule to fire
+const RULES: &[(Severity, &[&str], &str)] = &[
+    (Severity::High, &[RM_RECURSIVE, RM_CRITICAL_TARGET], "recursively deletes /, a system directory or your home directory"),
+    (Severity::Warning, &[RM_RECURSIVE, r"\brm\b[^;&|]*\s(?:-[a-zA-Z]*f[a-zA-Z]*|--force)(?:\s|$)"], "forced recursive delete"),
+    // any device but the harmless /dev/null, /dev/stdout and /dev/stderr (no lookahead in `regex`)
+    (Severity::High, &[r"\bdd\b[^;&|]*\bof=/dev/(?:[^ns\s]|n(?:[^u]|u[^l])|s(?:[^t]|t[^d])|std[^oe])"], "dd writes straight to a device"),
+    (Severity::High, &[r"\b(mkfs(\.\w+)?|wipefs)\b"], "repartitions or formats a disk"),
+    // fdisk or parted run as a command, unless every argument stays clear of `-l`/`--list`
+    (
+        Severity::High,
+        &[r"(?m)(?:^|[;&|]\s*|\bsudo\s+)(?:fdisk|parted)(?:\s+(?:-[^l\s;&|-][^\s;&|]*|--[^l\s;&|][^\s;&|]*|--l[^i\s;&|][^\s;&|]*|[^-\s;&|][^\s;&|]*))*\s*(?:$|[;&|])"],
+        "repartitions or formats a disk",
+    ),
+    (Severity::High, &[r"(?i)\bformat(\.com)?\s+[a-z]:"], "formats a drive"),
+    (Severity::High, &[r":\(\)\s*\{\s*:\s*\|\s*:\s*&\s*\}\s*;\s*:"], "fork bomb"),
+    (Severity::High, &[r"\b(curl|wget|fetch)\b[^|;&]*\|\s*(sudo\s+)?(ba|z|da|k|fi)?sh\b"], "pipes a download straight into a shell"),
+    (Severity::High, &[r"(?i)\b(iwr|irm|Invoke-WebRequest|Invoke-RestMethod)\b[^|;]*\|\s*(iex|Invoke-Expression)\b"], "pipes a download straight into Invoke-Expression"),
+    (Severity::High, &[r"(?i)\b(iex|Invoke-Expression)\s*\(?\s*\(?\s*(iwr|irm|Invoke-WebRequest|Invoke-RestMethod|New-Object\s+Net\.WebClient)"], "runs downloaded code with Invoke-Expression"),
+    (Severity::High, &[r"\bchmod\s+(-\S+\s+)*-[a-zA-Z]*R[a-zA-Z]*\s+(-\S+\s+)*0?777\b|\bchmod\s+0?777\s+(-\S+\s+)*-[a-zA-Z]*R"], "makes a whole tree world-writable"),
+    (Severity::High, &[r"(?im)(?:^|[;&|]\s*)(Remove-Item|ri|rm|rmdir|rd|del)\b", r"(?i)\s-Recurse\b", r"(?i)\s-Force\b"], "forced recursive Remove-Item"),
+    (Severity::High, &[r"\bgit\s+push\b[^;&|]*\s(--force|-f)(\s|$)"], "force-push rewrites remote history"),
+    (Severity::Warning, &[r"\bgit\s+push\b[^;&|]*\s--force-with-lease\b"], "force-push rewrites remote history"),
+    (Severity::Warning, &[r"\bgit\s+(reset\s+--hard|clean\s+-[a-zA-Z]*f)"], "discards uncommitted work"),
+    (Severity::High, &[r"(>|\btee\s+(-a\s+)?)\s*/(etc|boot|usr|bin|sbin|lib|lib64|sys|proc)/"], "writes into a system directory"),
+    (Severity::High, &[r">\s*/dev/(sd|hd|nvme|disk|mmcblk)"], "writes straight to a disk device"),
+    (Severity::Warning, &[r"\b(mv|cp|rsync)\b[^;&|]*\s/(etc|boot|usr|bin|sbin|lib)(/|\s|$)"], "modifies a system directory"),
+    (Severity::High, &[r"(?i)(>|\b(Set-Content|Add-Content|Out-File|Copy-Item|Move-Item)\b)[^;|]*C:\\Windows"], "writes into C:\\Windows"),
+    (Severity::Warning, &[r"(^|[;&|]\s*)sudo\b"], "runs with root privileges"),
+    (Severity::Warning, &[r"(?i)\b(shutdown|reboot|halt|poweroff|Stop-Computer|Restart-Computer)\b"], "shuts down or restarts the machine"),
+];
+
+/// Built-in rules plus the allow/deny lists from the config, compiled once
+pub struct RiskAnalyzer {
+    rules: Vec<(Severity, Vec<Regex>, String)>,
+    allow: Vec<Regex>,
+    deny: Vec<Regex>,
+}
+
+impl RiskAnalyzer {
+    pub fn new(cfg: &RiskConfig) -> Result<Self, Box<dyn std::error::Error>> {
+        let compile = |patterns: &[String], key: &str| -> Result<Vec<Regex>, String> {
+            patterns
+                .iter()
+                .map(|p| Regex::new(p).map_err(|e| format!("Invalid regex in risk.{key}: {e}")))
+                .collect()
+        };
+        let mut rules = Vec::new();
+        for (severity, patterns, reason) in RULES {
+            let regexes = patterns.iter().map(|p| Regex::new(p)).collect::<Result<_, _>>()?;
+            rules.push((*severity, regexes, reason.to_string()));
+        }
+        Ok(RiskAnalyzer {
+            rules,
+            allow: compile(&cfg.allow, "allow")?,
+            deny: compile(&cfg.deny, "deny")?,
+        })
+    }
+
+    /// Everything that looks dangerous about `command`, most severe first
+    pub fn analyze(&self, command: &str) -> Vec<Finding> {
+        let mut findings: Vec<Finding> = self
+            .deny
+            .iter()
+            .filter(|re| re.is_match(command))
+            .map(|re| Finding { severity: Severity::High, reason: format!("matches denylist pattern `{re}`") })
+            .collect();
+        if !self.allow.iter().any(|re| re.is_match(command)) {
+            for (severity, regexes, reason) in &self.rules {
+                if regexes.iter().all(|re| re.is_match(command)) && !findings.iter().any(|f| &f.reason == reason) {
+                    findings.push(Finding { severity: *severity, reason: reason.clone() });
+                }
+            }
+        }
+        findings.sort_by_key(|f| std::cmp::Reverse(f.severity));
+        findings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn worst(analyzer: &RiskAnalyzer, command: &str) -> Option<Severity> {
+        analyzer.analyze(command).first().map(|f| f.severity)
+    }
+
+    #[test]
+    fn flags_destructive_commands() {
+        let analyzer = RiskAnalyzer::new(&RiskConfig::default()).unwrap();
+        for command in [
+            "rm -rf /",
+            "sudo rm -rf --no-preserve-root /",
+            "rm -rf ~",
+            "rm -rf ~/*",
+            "rm -rf $HOME/*",
+            "rm -rf \"$HOME\"",
+            "rm -rf \"${HOME}\"/*",
+            "rm -r -f '/'",
+            "rm --recursive --force /",
+            "rm -r --force ~/",
+            "rm -rf /usr",
+            "sudo rm -rf /etc",
+            "rm -rf /*/",
+            "rm -rf /home/*",
+            "rm -rf /var/lib/docker",
+            "sudo fdisk /dev/sda",
+            "parted /dev/sdb --script mklabel gpt",
+            "dd if=image.iso of=/dev/sda bs=4M",
+            "mkfs.ext4 /dev/sdb1",
+            ":(){ :|:& };:",
+            "curl -fsSL https://example.com/install.sh | sh",
+            "wget -qO- https://example.com/x | sudo bash",
+            "chmod -R 777 /var/www",
+            "Remove-Item -Path C:\\temp -Recurse -Force",
+            "iwr https://example.com/x.ps1 | iex",
+            "git push origin main --force",
+            "echo 'nameserver 1.1.1.1' > /etc/resolv.conf",
+            "cd C:\\temp; ri build -Recurse -Force",
+        ] {
+            assert_eq!(worst(&analyzer, command), Some(Severity::High), "{command}");
+        }
+    }
+
+    #[test]
+    fn leaves_everyday_commands_alone() {
+        let analyzer = RiskAnalyzer::new(&RiskConfig::default()).unwrap();
+        for command in [
+            "ls -la",
+            "rm build.log",
+            "find . -name '*.tmp' -delete",
+            "git push origin main",
+            "curl -s https://example.com | jq .",
+            "Get-ChildItem -Recurse -Filter *.log",
+            "Get-ChildItem C:\\del -Recurse -Force",
+            "fdisk -l",
+            "parted --list",
+            "parted -l",
+        ] {
+            assert!(analyzer.analyze(command).is_empty(), "{command}");
+        }
+        assert_eq!(worst(&analyzer, "rm -rf ./target"), Some(Severity::Warning));
+        assert_eq!(worst(&analyzer, "rm --recursive --force ./build"), Some(Severity::Warning));
+        assert_eq!(worst(&analyzer, "rm -rf ~/projects/old"), Some(Severity::Warning));
+        assert_eq!(worst(&analyzer, "rm -rf \"$HOME/.cache/thumbnails\""), Some(Severity::Warning));
+        for command in ["dd if=/dev/zero of=/dev/null bs=1M count=100", "dd if=disk.img of=/dev/stdout", "dd if=x of=/dev/stderr"] {
+            assert!(analyzer.analyze(command).is_empty(), "{command}");
+        }
+        assert_eq!(worst(&analyzer, "dd if=/dev/zero of=/dev/nvme0n1"), Some(Severity::High));
+        assert_eq!(worst(&analyzer, "dd if=/dev/zero of=/dev/sdb"), Some(Severity::High));
+        assert_eq!(worst(&analyzer, "git push --force-with-lease"), Some(Severity::Warning));
+        assert_eq!(worst(&analyzer, "sudo fdisk -l /dev/sda"), Some(Severity::Warning));
+    }
+
+    #[test]
+    fn config_lists_extend_the_rules() {
+        let cfg = RiskConfig {
+            allow: vec![r"^rm -rf ./target$".to_string()],
+            deny: vec![r"\bkubectl\s+delete\b".to_string()],
+        };
+        let analyzer = RiskAnalyzer::new(&cfg).unwrap();
+        assert!(analyzer.analyze("rm -rf ./target").is_empty());
+        assert_eq!(worst(&analyzer, "kubectl delete ns prod"), Some(Severity::High));
+        assert!(RiskAnalyzer::new(&RiskConfig { allow: vec!["(".to_string()], deny: vec![] }).is_err());
+    }
+}
@@ -0,0 +1,112 @@
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// How much of stderr is kept for feeding back to the model
+const KEPT_STDERR_BYTES: usize = 4000;
+
+/// Set while a child process owns the terminal
+static CHILD_RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// Whether a command (or the editor) is running in the foreground right now
+pub fn child_running() -> bool {
+    CHILD_RUNNING.load(Ordering::SeqCst)
+}
+
+/// Marks a child as running until dropped
+pub struct ChildGuard;
+
+impl ChildGuard {
+    pub fn new() -> Self {
+        CHILD_RUNNING.store(true, Ordering::SeqCst);
+        ChildGuard
+    }
+}
+
+impl Drop for ChildGuard {
+    fn drop(&mut self) {
+        CHILD_RUNNING.store(false, Ordering::SeqCst);
+    }
+}
+
+/// What came out of running a command
+pub struct Outcome {
+    pub status: ExitStatus,
+    /// Tail of stderr, only collected when asked for
+    pub stderr: String,
+}
+
+/// Copy a child's output stream to the terminal (and the capture file) as it arrives,
+/// returning what was read when `keep` is set
+fn tee(mut from: impl Read, mut to: impl Write, file: Option<Arc<Mutex<File>>>, keep: bool) -> io::Result<Vec<u8>> {
+    let mut buf = [0u8; 8192];
+    let mut kept = Vec::new();
+    loop {
+        let n = from.read(&mut buf)?;
+        if n == 0 {
+            return Ok(kept);
+        }
+        to.write_all(&buf[..n])?;
+        to.flush()?;
+        if let Some(file) = &file {
+            file.lock().unwrap().write_all(&buf[..n])?;
+        }
+        if keep {
+            kept.extend_from_slice(&buf[..n]);
+            if kept.len() > 2 * KEPT_STDERR_BYTES {
+                kept.drain(..kept.len() - KEPT_STDERR_BYTES);
+            }
+        }
+    }
+}
+
+/// Run the command, optionally also writing its stdout and stderr to `capture`
+/// and keeping the tail of stderr
+pub fn run(mut cmd: Command, capture: Option<&Path>, keep_stderr: bool) -> io::Result<Outcome> {
+    let _guard = ChildGuard::new();
+    if capture.is_none() && !keep_stderr {
+        // inherit the terminal so interactive programs keep working
+        let status = cmd.spawn()?.wait()?;
+        return Ok(Outcome { status, stderr: String::new() });
+    }
+    let file = match capture {
+        Some(path) => Some(Arc::new(Mutex::new(File::create(path)?))),
+        None => None,
+    };
+    if file.is_some() {
+        cmd.stdout(Stdio::piped());
+    }
+    let mut child = cmd.stderr(Stdio::piped()).spawn()?;
+    let out = child.stdout.take().map(|stdout| {
+        let out_file = file.clone();
+        thread::spawn(move || tee(stdout, io::stdout(), out_file, false))
+    });
+    let stderr = child.stderr.take().expect("stderr is piped");
+    let err = thread::spawn(move || tee(stderr, io::stderr(), file, keep_stderr));
+    let status = child.wait()?;
+    if let Some(out) = out {
+        out.join().expect("stdout copier panicked")?;
+    }
+    let kept = err.join().expect("stderr copier panicked")?;
+    let start = kept.len().saturating_sub(KEPT_STDERR_BYTES);
+    Ok(Outcome { status, stderr: String::from_utf8_lossy(&kept[start..]).into_owned() })
+}
+
+/// Exit code aido should mirror: the child's own code, or 128 + signal like a shell reports it
+pub fn exit_code(status: ExitStatus) -> i32 {
+    if let Some(code) = status.code() {
+        return code;
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return 128 + signal;
+        }
+    }
+    1
+}
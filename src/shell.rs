@@ -0,0 +1,273 @@
+use clap::ValueEnum;
+use std::collections::HashMap;
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Supported shells for execution
+#[allow(clippy::enum_variant_names)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug, ValueEnum)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+    /// POSIX sh
+    Sh,
+    #[value(alias = "nu")]
+    Nushell,
+    #[value(alias = "powershell", alias = "pwsh")]
+    PowerShell,
+    Cmd,
+}
+
+impl Shell {
+    /// Name used when talking to the model
+    pub fn display_name(self) -> &'static str {
+        match self {
+            Shell::Bash => "bash",
+            Shell::Zsh => "zsh",
+            Shell::Fish => "fish",
+            Shell::Sh => "POSIX sh",
+            Shell::Nushell => "Nushell",
+            Shell::PowerShell => "PowerShell",
+            Shell::Cmd => "cmd.exe",
+        }
+    }
+
+    /// Name as accepted by `--shell`
+    pub fn cli_name(self) -> String {
+        self.to_possible_value().map(|v| v.get_name().to_string()).unwrap_or_default()
+    }
+
+    /// File extension syntect uses to pick a highlighter
+    pub fn ext(self) -> &'static str {
+        match self {
+            Shell::Bash | Shell::Sh => "sh",
+            Shell::Zsh => "zsh",
+            Shell::Fish => "fish",
+            Shell::Nushell => "nu",
+            Shell::PowerShell => "ps1",
+            Shell::Cmd => "bat",
+        }
+    }
+
+    /// Extra guidance for the system prompt, steering the model away from the wrong dialect
+    pub fn prompt_notes(self) -> &'static str {
+        match self {
+            Shell::Bash => "",
+            Shell::Zsh => "Use zsh syntax; zsh globbing and parameter expansion are available.",
+            Shell::Fish => "Use fish syntax: `set -x VAR value` instead of export, `(cmd)` instead of `$(cmd)`, `; and`/`; or` or `&&`/`||`, and no bash-only constructs.",
+            Shell::Sh => "Use only POSIX sh features: no arrays, `[[ ]]`, `$'...'`, brace expansion or other bash-isms.",
+            Shell::Nushell => "Use Nushell syntax and its structured commands (ls, where, get, each); it is not POSIX-compatible.",
+            Shell::PowerShell => "Prefer PowerShell cmdlets over external Unix tools.",
+            Shell::Cmd => "Use cmd.exe syntax as typed at the prompt (single % for variables), not a batch file.",
+        }
+    }
+
+    /// Executable names to look for on PATH, in order of preference
+    fn programs(self) -> &'static [&'static str] {
+        match self {
+            Shell::Bash => &["bash"],
+            Shell::Zsh => &["zsh"],
+            Shell::Fish => &["fish"],
+            Shell::Sh => &["sh"],
+            Shell::Nushell => &["nu"],
+            // Windows PowerShell only exists on Windows; PowerShell 7 is `pwsh` everywhere
+            Shell::PowerShell if cfg!(windows) => &["pwsh", "powershell"],
+            Shell::PowerShell => &["pwsh"],
+            Shell::Cmd => &["cmd"],
+        }
+    }
+
+    /// Locate the interpreter, preferring a path from `shell_paths` in the config
+    pub fn find_interpreter(self, shell_paths: &HashMap<String, String>) -> Result<PathBuf, String> {
+        let configured = shell_paths
+            .iter()
+            .find(|(name, _)| shell_from_name(name) == Some(self))
+            .map(|(_, path)| path);
+        if let Some(path) = configured {
+            return find_executable(path)
+                .ok_or_else(|| format!("{} interpreter '{path}' from shell_paths in the config was not found", self.display_name()));
+        }
+        self.programs().iter().find_map(|p| find_executable(p)).ok_or_else(|| {
+            format!(
+                "{} is not installed (looked for {} on PATH). Install it, pick another --shell, or set its path under \"shell_paths\" in the config.",
+                self.display_name(),
+                self.programs().join(" or ")
+            )
+        })
+    }
+
+    /// Interpreter invocation that runs `script` as a single command line
+    pub fn command(self, interpreter: &Path, script: &str) -> Command {
+        let args: &[&str] = match self {
+            Shell::Bash | Shell::Zsh | Shell::Fish | Shell::Sh | Shell::Nushell => &["-c"],
+            Shell::PowerShell => &["-NoProfile", "-Command"],
+            Shell::Cmd => &["/C"],
+        };
+        let mut cmd = Command::new(interpreter);
+        cmd.args(args).arg(script);
+        cmd
+    }
+}
+
+/// Resolve a program name against PATH (and PATHEXT on Windows); paths are checked as-is
+pub fn find_executable(name: &str) -> Option<PathBuf> {
+    let with_exts = |base: PathBuf| -> Option<PathBuf> {
+        if base.is_file() {
+            return Some(base);
+        }
+        if cfg!(windows) && base.extension().is_none() {
+            let exts = env::var("PATHEXT").unwrap_or_else(|_| ".COM;.EXE;.BAT;.CMD".to_string());
+            return exts
+                .split(';')
+                .filter(|e| !e.is_empty())
+                .map(|e| base.with_extension(e.trim_start_matches('.')))
+                .find(|p| p.is_file());
+        }
+        None
+    };
+    if name.contains(['/', '\\']) {
+        return with_exts(PathBuf::from(name));
+    }
+    env::split_paths(&env::var_os("PATH")?).find_map(|dir| with_exts(dir.join(name)))
+}
+
+/// What shell detection looks at, gathered up front so it can be faked in tests
+pub struct ShellEnv {
+    /// `default_shell` from the config
+    pub configured: Option<String>,
+    /// Name or path of the process that launched aido
+    pub parent_process: Option<String>,
+    /// `$SHELL`
+    pub shell_var: Option<String>,
+    pub os: &'static str,
+}
+
+impl ShellEnv {
+    /// Inspect the real environment
+    pub fn current(configured: Option<String>) -> Self {
+        ShellEnv {
+            configured,
+            parent_process: parent_process_name(),
+            shell_var: std::env::var("SHELL").ok().filter(|v| !v.is_empty()),
+            os: std::env::consts::OS,
+        }
+    }
+}
+
+/// Map a shell name, executable name or path (`/usr/bin/zsh`, `-bash`, `pwsh.exe`) to a shell
+pub fn shell_from_name(name: &str) -> Option<Shell> {
+    if let Ok(shell) = Shell::from_str(name.trim(), true) {
+        return Some(shell);
+    }
+    let base = name.trim().rsplit(['/', '\\']).next().unwrap_or_default();
+    let base = base.trim_start_matches('-').to_lowercase();
+    match base.strip_suffix(".exe").unwrap_or(&base) {
+        "bash" => Some(Shell::Bash),
+        "zsh" => Some(Shell::Zsh),
+        "fish" => Some(Shell::Fish),
+        "sh" | "dash" | "ash" | "ksh" | "mksh" => Some(Shell::Sh),
+        "nu" | "nushell" => Some(Shell::Nushell),
+        "pwsh" | "powershell" => Some(Shell::PowerShell),
+        "cmd" => Some(Shell::Cmd),
+        _ => None,
+    }
+}
+
+/// Pick a shell when `--shell` isn't given: config, then parent process, then `$SHELL`, then the OS default
+pub fn detect_shell(env: &ShellEnv) -> Shell {
+    if let Some(name) = &env.configured {
+        match shell_from_name(name) {
+            Some(shell) => return shell,
+            None => eprintln!("Warning: unknown default_shell '{name}' in config, detecting instead"),
+        }
+    }
+    env.parent_process
+        .as_deref()
+        .and_then(shell_from_name)
+        .or_else(|| env.shell_var.as_deref().and_then(shell_from_name))
+        .unwrap_or(match env.os {
+            "windows" => Shell::PowerShell,
+            _ => Shell::Bash,
+        })
+}
+
+/// Executable name of the parent process, where the platform makes that cheap
+#[cfg(target_os = "linux")]
+fn parent_process_name() -> Option<String> {
+    let ppid = std::os::unix::process::parent_id();
+    let comm = std::fs::read_to_string(format!("/proc/{ppid}/comm")).ok()?;
+    Some(comm.trim().to_string())
+}
+
+/// Executable name of the parent process, where the platform makes that cheap
+#[cfg(all(unix, not(target_os = "linux")))]
+fn parent_process_name() -> Option<String> {
+    let ppid = std::os::unix::process::parent_id();
+    let out = Command::new("ps").args(["-o", "comm=", "-p", &ppid.to_string()]).output().ok()?;
+    let name = String::from_utf8_lossy(&out.stdout).trim().to_string();
+    (!name.is_empty()).then_some(name)
+}
+
+/// Executable name of the parent process, where the platform makes that cheap
+#[cfg(not(unix))]
+fn parent_process_name() -> Option<String> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fake_env(configured: Option<&str>, parent: Option<&str>, shell_var: Option<&str>, os: &'static str) -> ShellEnv {
+        ShellEnv {
+            configured: configured.map(String::from),
+            parent_process: parent.map(String::from),
+            shell_var: shell_var.map(String::from),
+            os,
+        }
+    }
+
+    #[test]
+    fn names_and_paths_map_to_shells() {
+        assert_eq!(shell_from_name("/usr/bin/zsh"), Some(Shell::Zsh));
+        assert_eq!(shell_from_name("-bash"), Some(Shell::Bash));
+        assert_eq!(shell_from_name("C:\\Program Files\\PowerShell\\7\\pwsh.exe"), Some(Shell::PowerShell));
+        assert_eq!(shell_from_name("power-shell"), Some(Shell::PowerShell));
+        assert_eq!(shell_from_name("dash"), Some(Shell::Sh));
+        assert_eq!(shell_from_name("nu"), Some(Shell::Nushell));
+        assert_eq!(shell_from_name("aido"), None);
+    }
+
+    #[test]
+    fn config_wins_over_environment() {
+        let env = fake_env(Some("fish"), Some("zsh"), Some("/bin/bash"), "linux");
+        assert_eq!(detect_shell(&env), Shell::Fish);
+    }
+
+    #[test]
+    fn unknown_config_falls_through() {
+        let env = fake_env(Some("tcsh"), None, Some("/bin/zsh"), "linux");
+        assert_eq!(detect_shell(&env), Shell::Zsh);
+    }
+
+    #[test]
+    fn parent_process_wins_over_shell_var() {
+        let env = fake_env(None, Some("fish"), Some("/bin/bash"), "linux");
+        assert_eq!(detect_shell(&env), Shell::Fish);
+    }
+
+    #[test]
+    fn unrecognised_parent_uses_shell_var() {
+        let env = fake_env(None, Some("tmux: server"), Some("/usr/local/bin/fish"), "macos");
+        assert_eq!(detect_shell(&env), Shell::Fish);
+    }
+
+    #[test]
+    fn falls_back_per_os() {
+        assert_eq!(detect_shell(&fake_env(None, None, None, "linux")), Shell::Bash);
+        assert_eq!(detect_shell(&fake_env(None, None, None, "macos")), Shell::Bash);
+        assert_eq!(detect_shell(&fake_env(None, Some("explorer.exe"), None, "windows")), Shell::PowerShell);
+    }
+}
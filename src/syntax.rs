@@ -0,0 +1,56 @@
+use crate::shell::Shell;
+use std::collections::HashMap;
+use std::process::{Command, Stdio};
+
+/// Parses `$env:AIDO_SCRIPT` with PowerShell's own parser and prints any errors
+const PWSH_CHECK: &str = "$errors = $null; \
+    [void][System.Management.Automation.Language.Parser]::ParseInput($env:AIDO_SCRIPT, [ref]$null, [ref]$errors); \
+    $errors | ForEach-Object { \"line $($_.Extent.StartLineNumber): $($_.Message)\" }; \
+    if ($errors) { exit 1 }";
+
+/// Parse `command` with the shell itself without running it; the error message when it doesn't parse
+///
+/// bash, zsh and sh use `-n`, fish `--no-execute` and PowerShell its parser API. Nushell and cmd
+/// aren't checked, and neither is anything when the interpreter can't be found.
+pub fn check(shell: Shell, shell_paths: &HashMap<String, String>, command: &str) -> Option<String> {
+    let interpreter = shell.find_interpreter(shell_paths).ok()?;
+    let mut cmd = Command::new(&interpreter);
+    match shell {
+        Shell::Bash | Shell::Zsh | Shell::Sh => cmd.args(["-n", "-c", command]),
+        Shell::Fish => cmd.args(["--no-execute", "-c", command]),
+        Shell::PowerShell => cmd.args(["-NoProfile", "-NonInteractive", "-Command", PWSH_CHECK]).env("AIDO_SCRIPT", command),
+        Shell::Nushell | Shell::Cmd => return None,
+    };
+    let output = cmd.stdin(Stdio::null()).output().ok()?;
+    if output.status.success() {
+        return None;
+    }
+    let text = [output.stderr, output.stdout].concat();
+    let text = String::from_utf8_lossy(&text);
+    // drop the `bash: -c: ` style prefix, which only says where the script came from
+    let message: Vec<&str> = text
+        .lines()
+        .map(|l| l.trim())
+        .filter(|l| !l.is_empty())
+        .map(|l| l.split_once(": -c: ").map_or(l, |(_, rest)| rest))
+        .collect();
+    Some(if message.is_empty() { format!("{} couldn't parse it", shell.display_name()) } else { message.join("; ") })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shell::find_executable;
+
+    #[test]
+    fn reports_bash_syntax_errors() {
+        if find_executable("bash").is_none() {
+            return;
+        }
+        let paths = HashMap::new();
+        assert!(check(Shell::Bash, &paths, "for f in *.txt; do echo \"$f\"; done").is_none());
+        let error = check(Shell::Bash, &paths, "Here's the command: ls -la").expect("unbalanced quote");
+        assert!(!error.starts_with("bash"), "{error}");
+        assert!(check(Shell::Bash, &paths, "if true; then echo hi").is_some());
+    }
+}
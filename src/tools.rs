@@ -0,0 +1,344 @@
+use crate::shell::{find_executable, Shell};
+use regex::Regex;
+
+/// Reserved words that can appear where a command is expected
+const KEYWORDS: &[&str] = &[
+    "if", "then", "else", "elif", "fi", "do", "done", "while", "until", "esac", "in", "!", "{", "}", "[[", "]]", "coproc",
+];
+
+/// Keywords whose following words aren't commands until the next separator
+const HEADER_KEYWORDS: &[&str] = &["for", "case", "select", "function", "foreach", "switch"];
+
+/// Builtins shared by sh, bash and zsh
+const POSIX_BUILTINS: &[&str] = &[
+    ".", ":", "[", "alias", "bg", "break", "cd", "continue", "echo", "eval", "exit", "export", "false", "fc", "fg", "getopts", "hash",
+    "jobs", "kill", "local", "printf", "pwd", "read", "readonly", "return", "set", "shift", "test", "times", "trap", "true", "type",
+    "ulimit", "umask", "unalias", "unset", "wait",
+];
+
+const BASH_BUILTINS: &[&str] = &[
+    "builtin", "caller", "compgen", "complete", "declare", "dirs", "disown", "enable", "help", "history", "let", "logout", "mapfile",
+    "popd", "pushd", "readarray", "shopt", "source", "suspend", "typeset",
+];
+
+const ZSH_BUILTINS: &[&str] = &[
+    "autoload", "bindkey", "builtin", "declare", "dirs", "disown", "emulate", "functions", "history", "let", "noglob", "popd", "print",
+    "pushd", "rehash", "repeat", "setopt", "source", "typeset", "unsetopt", "vared", "whence", "where", "which", "zle", "zmodload",
+];
+
+const FISH_BUILTINS: &[&str] = &[
+    "abbr", "and", "argparse", "begin", "bg", "bind", "block", "break", "builtin", "cd", "commandline", "complete", "contains",
+    "continue", "count", "disown", "echo", "else", "emit", "end", "eval", "exit", "false", "fg", "fish_add_path", "functions",
+    "history", "jobs", "math", "not", "or", "path", "printf", "pwd", "random", "read", "realpath", "return", "set", "set_color",
+    "source", "status", "string", "test", "true", "type", "ulimit", "wait",
+];
+
+/// Default PowerShell aliases and keywords; cmdlets (Verb-Noun) are recognised by their dash
+const POWERSHELL_BUILTINS: &[&str] = &[
+    "%", "?", "begin", "break", "cat", "catch", "cd", "chdir", "clear", "cls", "continue", "copy", "cp", "del", "dir", "do", "echo",
+    "else", "elseif", "end", "erase", "exit", "filter", "finally", "foreach", "function", "gc", "gci", "gcm", "gi", "gl", "gm", "gp",
+    "gps", "group", "gsv", "gv", "iex", "irm", "iwr", "kill", "ls", "measure", "mkdir", "move", "mv", "param", "popd", "process",
+    "ps", "pushd", "pwd", "ri", "rm", "rmdir", "select", "set", "sl", "sleep", "sort", "start", "tee", "throw", "try", "type",
+    "where", "write",
+];
+
+const CMD_BUILTINS: &[&str] = &[
+    "assoc", "break", "call", "cd", "chdir", "cls", "color", "copy", "date", "defined", "del", "dir", "do", "echo", "else",
+    "endlocal", "erase", "errorlevel", "exist", "exit", "ftype", "goto", "if", "in", "md", "mkdir", "mklink", "move", "not", "path",
+    "pause", "popd", "prompt", "pushd", "rd", "ren", "rename", "rmdir", "set", "setlocal", "shift", "start", "time", "title",
+    "type", "ver", "verify", "vol",
+];
+
+/// Programs that run the command after them: name, options that take a value, and
+/// positional arguments before the command (`timeout 5 cmd`)
+const WRAPPERS: &[(&str, &[&str], usize)] = &[
+    ("sudo", &["-u", "-g", "-C", "-D", "-h", "-p", "-U", "-r", "-t"], 0),
+    ("doas", &["-u", "-C"], 0),
+    ("env", &["-u", "-C", "-S"], 0),
+    ("nice", &["-n"], 0),
+    ("nohup", &[], 0),
+    ("time", &[], 0),
+    ("exec", &["-a"], 0),
+    ("command", &[], 0),
+    ("builtin", &[], 0),
+    ("xargs", &["-I", "-n", "-P", "-L", "-d", "-E", "-s", "-a"], 0),
+    ("timeout", &["-s", "-k", "--signal", "--kill-after"], 1),
+    ("watch", &["-n", "--interval"], 0),
+    ("stdbuf", &[], 0),
+];
+
+/// Optional tools worth telling the model about, since whether they're installed varies
+pub const RELEVANT_TOOLS: &[&str] = &[
+    "rg", "fd", "fzf", "jq", "yq", "bat", "eza", "sd", "delta", "gawk", "parallel", "tree", "curl", "wget", "http", "rsync", "git",
+    "gh", "docker", "podman", "kubectl", "python3", "node", "ffmpeg", "convert", "magick", "zip", "unzip", "7z", "zstd", "xz",
+];
+
+enum Token {
+    Word(String),
+    /// Something that starts a new command: `|`, `;`, `&&`, `(`, `$(`, a newline, ...
+    Break,
+    /// `>` or `<`: the next word is a file
+    Redirect,
+}
+
+/// Split a command line into words (quotes removed) and separators; good enough to find
+/// command names, not a full shell parser
+fn tokenize(script: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut in_double = false;
+    // open parentheses; true where a `$(` interrupted a double-quoted string
+    let mut parens: Vec<bool> = Vec::new();
+    let mut chars = script.chars().peekable();
+    let flush = |word: &mut String, in_word: &mut bool, tokens: &mut Vec<Token>| {
+        if *in_word {
+            tokens.push(Token::Word(std::mem::take(word)));
+            *in_word = false;
+        }
+    };
+    while let Some(c) = chars.next() {
+        if c == '$' && chars.peek() == Some(&'(') {
+            chars.next();
+            if chars.peek() == Some(&'(') {
+                // arithmetic `$(( ... ))` runs nothing
+                let mut depth = 1;
+                for c in chars.by_ref() {
+                    depth += match c {
+                        '(' => 1,
+                        ')' => -1,
+                        _ => 0,
+                    };
+                    if depth == 0 {
+                        break;
+                    }
+                }
+                in_word = true;
+                continue;
+            }
+            flush(&mut word, &mut in_word, &mut tokens);
+            tokens.push(Token::Break);
+            parens.push(in_double);
+            in_double = false;
+            continue;
+        }
+        if in_double {
+            match c {
+                '"' => in_double = false,
+                '\\' => word.extend(chars.next()),
+                c => word.push(c),
+            }
+            continue;
+        }
+        match c {
+            '\'' => {
+                in_word = true;
+                word.extend(chars.by_ref().take_while(|&c| c != '\''));
+            }
+            '"' => {
+                in_word = true;
+                in_double = true;
+            }
+            '\\' => {
+                in_word = true;
+                word.extend(chars.next().filter(|&c| c != '\n'));
+            }
+            '#' if !in_word => {
+                // comment up to the end of the line
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        tokens.push(Token::Break);
+                        break;
+                    }
+                }
+            }
+            '(' | ')' => {
+                flush(&mut word, &mut in_word, &mut tokens);
+                tokens.push(Token::Break);
+                if c == '(' {
+                    parens.push(false);
+                } else if parens.pop() == Some(true) {
+                    // back inside the string the substitution was in
+                    in_word = true;
+                    in_double = true;
+                }
+            }
+            '|' | '&' | ';' | '`' | '\n' => {
+                flush(&mut word, &mut in_word, &mut tokens);
+                tokens.push(Token::Break);
+            }
+            '<' | '>' => {
+                // `2>` and `&>` are part of the redirection, not words
+                if word.chars().all(|c| c.is_ascii_digit()) {
+                    word.clear();
+                    in_word = false;
+                }
+                flush(&mut word, &mut in_word, &mut tokens);
+                while chars.next_if(|&c| c == '>' || c == '<' || c == '&').is_some() {}
+                // `2>&1` has no file after it
+                if chars.peek().is_some_and(|c| c.is_ascii_digit() || *c == '-') {
+                    while chars.next_if(|c| c.is_ascii_digit() || *c == '-').is_some() {}
+                } else {
+                    tokens.push(Token::Redirect);
+                }
+            }
+            c if c.is_whitespace() => flush(&mut word, &mut in_word, &mut tokens),
+            c => {
+                in_word = true;
+                word.push(c);
+            }
+        }
+    }
+    flush(&mut word, &mut in_word, &mut tokens);
+    tokens
+}
+
+/// Names of functions the script defines itself (`f() { ...; }`, `function f`)
+fn defined_functions(script: &str) -> Vec<String> {
+    let re = Regex::new(r"(?:^|[\s;&|{(])(?:function\s+([A-Za-z_][\w.:-]*)|([A-Za-z_][\w.:-]*)\s*\(\s*\))").expect("valid regex");
+    re.captures_iter(script).filter_map(|c| c.get(1).or(c.get(2))).map(|m| m.as_str().to_string()).collect()
+}
+
+/// Program names in command position, in order of appearance and without duplicates
+pub fn command_names(script: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    let mut expect_cmd = true;
+    let mut skip_to_break = false;
+    let mut skip_next = false;
+    // inside a wrapper like `sudo -u root cmd`: its value-taking options and positional count
+    let mut wrapper: Option<(&[&str], usize)> = None;
+    for token in tokenize(script) {
+        let word = match token {
+            Token::Break => {
+                expect_cmd = true;
+                skip_to_break = false;
+                skip_next = false;
+                wrapper = None;
+                continue;
+            }
+            Token::Redirect => {
+                skip_next = true;
+                continue;
+            }
+            Token::Word(word) => word,
+        };
+        if std::mem::take(&mut skip_next) || skip_to_break || !expect_cmd || word.is_empty() {
+            continue;
+        }
+        if let Some((value_opts, positional)) = wrapper.as_mut() {
+            if word.starts_with('-') {
+                skip_next = value_opts.contains(&word.as_str());
+                continue;
+            }
+            if *positional > 0 {
+                *positional -= 1;
+                continue;
+            }
+        }
+        // `FOO=bar cmd`
+        if word.split_once('=').is_some_and(|(name, _)| !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_')) {
+            continue;
+        }
+        if KEYWORDS.contains(&word.as_str()) {
+            continue;
+        }
+        if HEADER_KEYWORDS.contains(&word.to_lowercase().as_str()) {
+            skip_to_break = true;
+            continue;
+        }
+        wrapper = WRAPPERS.iter().find(|(name, _, _)| *name == word).map(|(_, opts, positional)| (*opts, *positional));
+        if wrapper.is_none() {
+            expect_cmd = false;
+        }
+        if !names.contains(&word) {
+            names.push(word);
+        }
+    }
+    names
+}
+
+fn builtins(shell: Shell) -> Vec<&'static str> {
+    match shell {
+        Shell::Sh => POSIX_BUILTINS.to_vec(),
+        Shell::Bash => [POSIX_BUILTINS, BASH_BUILTINS].concat(),
+        Shell::Zsh => [POSIX_BUILTINS, BASH_BUILTINS, ZSH_BUILTINS].concat(),
+        Shell::Fish => FISH_BUILTINS.to_vec(),
+        Shell::PowerShell => POWERSHELL_BUILTINS.to_vec(),
+        Shell::Cmd => CMD_BUILTINS.to_vec(),
+        Shell::Nushell => Vec::new(),
+    }
+}
+
+/// Whether `name` can't be resolved by looking at PATH at all: variables, paths, globs
+fn unresolvable(name: &str) -> bool {
+    name.is_empty() || name.contains(['$', '/', '\\', '*', '?', '[', '%', '{', '}', '='])
+}
+
+/// Programs the command uses that are neither builtins for `shell` nor on PATH
+///
+/// Nushell is skipped: most of what it runs are its own commands.
+pub fn missing_tools(script: &str, shell: Shell) -> Vec<String> {
+    if shell == Shell::Nushell {
+        return Vec::new();
+    }
+    let builtins = builtins(shell);
+    let functions = defined_functions(script);
+    let case_insensitive = matches!(shell, Shell::PowerShell | Shell::Cmd);
+    command_names(script)
+        .into_iter()
+        .filter(|name| !unresolvable(name) && !functions.contains(name))
+        .filter(|name| {
+            let key = if case_insensitive { name.to_lowercase() } else { name.clone() };
+            !builtins.contains(&key.as_str())
+        })
+        // PowerShell cmdlets are Verb-Noun
+        .filter(|name| !(shell == Shell::PowerShell && name.contains('-')))
+        .filter(|name| find_executable(name).is_none())
+        .collect()
+}
+
+/// The program the command starts with, when it's neither a builtin nor on PATH; usually
+/// a sign the model answered with prose (`Here is the command: ls -la`)
+pub fn missing_first_program(script: &str, shell: Shell) -> Option<String> {
+    let first = command_names(script).into_iter().next()?;
+    missing_tools(script, shell).contains(&first).then_some(first)
+}
+
+/// Which of the optional tools are installed, for the system prompt
+pub fn tool_hints() -> String {
+    let (installed, missing): (Vec<&str>, Vec<&str>) = RELEVANT_TOOLS.iter().partition(|tool| find_executable(tool).is_some());
+    let mut hints = String::new();
+    if !installed.is_empty() {
+        hints.push_str(&format!("Installed tools you may use: {}.", installed.join(", ")));
+    }
+    if !missing.is_empty() {
+        hints.push_str(&format!(" Not installed, so don't use them: {}.", missing.join(", ")));
+    }
+    hints.trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_commands_in_pipelines_and_substitutions() {
+        assert_eq!(
+            command_names(r#"FOO=1 sudo -u root find . -name '*.rs' | xargs -I {} wc -l {} && echo "$(date +%F)" > out.txt 2>&1"#),
+            ["sudo", "find", "xargs", "wc", "echo", "date"]
+        );
+        assert_eq!(command_names("for f in *.log; do gzip \"$f\"; done"), ["gzip"]);
+        assert_eq!(command_names("timeout 5 curl -s example.com # fetch it"), ["timeout", "curl"]);
+        assert_eq!(command_names("cat < input.txt | jq '.a | .b'"), ["cat", "jq"]);
+        assert_eq!(command_names(r#"echo "sum $((1 + 2)) at $(date "+%H:%M")"; ls"#), ["echo", "date", "ls"]);
+    }
+
+    #[test]
+    fn skips_builtins_and_own_functions() {
+        assert!(missing_tools("cd /tmp && export X=1; greet() { echo hi; }; greet", Shell::Bash).is_empty());
+        assert!(missing_tools("Get-ChildItem | ForEach-Object { $_.Name }", Shell::PowerShell).is_empty());
+        assert_eq!(missing_tools("surely-not-installed-aido-tool --help", Shell::Bash), ["surely-not-installed-aido-tool"]);
+        assert_eq!(missing_first_program("Here is the command: ls -la", Shell::Bash).as_deref(), Some("Here"));
+        assert_eq!(missing_first_program("cd /tmp && surely-not-installed-aido-tool", Shell::Bash), None);
+    }
+}